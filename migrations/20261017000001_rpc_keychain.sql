@@ -0,0 +1,17 @@
+-- user-001: RPC identities are stored in Postgres instead of in memory
+-- user-002: ``used`` is bumped in the database so concurrent requests cannot go past ``max_uses``
+CREATE TABLE rpc_keychain (
+    -- SHA-256 of the identity handed to the user, the identity itself is never stored
+    identity_hash TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    allowed_methods TEXT[] NOT NULL,
+    max_uses INTEGER NOT NULL,
+    used INTEGER NOT NULL DEFAULT 0,
+    reason TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (used <= max_uses)
+);
+
+CREATE INDEX rpc_keychain_user_id_idx ON rpc_keychain (user_id);
+CREATE INDEX rpc_keychain_expires_at_idx ON rpc_keychain (expires_at);
@@ -0,0 +1,18 @@
+-- user-007: outbox of channel posts, delivered with retries by the notifications task
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    channel_id TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    -- ``ON CONFLICT (dedup_key) DO NOTHING`` needs this to be unique, NULLs never conflict
+    dedup_key TEXT UNIQUE,
+    -- pending, sent or dead
+    state TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX notifications_pending_idx ON notifications (next_attempt_at) WHERE state = 'pending';
+CREATE INDEX notifications_created_at_idx ON notifications (created_at);
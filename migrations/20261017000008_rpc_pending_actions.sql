@@ -0,0 +1,16 @@
+-- user-008: methods that need a second approval are saved here until someone approves or rejects them
+CREATE TABLE rpc_pending_actions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    method TEXT NOT NULL,
+    data JSONB NOT NULL,
+    user_id TEXT NOT NULL,
+    -- pending, running (approved and being run), approved, failed or rejected
+    state TEXT NOT NULL DEFAULT 'pending',
+    decided_by TEXT,
+    decided_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The user who approved the method, for methods run through a quorum
+ALTER TABLE rpc_logs ADD COLUMN approved_by TEXT;
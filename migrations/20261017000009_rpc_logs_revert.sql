@@ -0,0 +1,7 @@
+-- user-009: reversible methods save what they overwrote (and what they wrote) so Revert can restore it
+ALTER TABLE rpc_logs ADD COLUMN before_image JSONB;
+ALTER TABLE rpc_logs ADD COLUMN after_image JSONB;
+-- Set on a Revert, the log it reverted
+ALTER TABLE rpc_logs ADD COLUMN reverts UUID REFERENCES rpc_logs (id);
+-- Set on a reverted log, the Revert that undid it
+ALTER TABLE rpc_logs ADD COLUMN reverted_by UUID REFERENCES rpc_logs (id);
@@ -0,0 +1,19 @@
+-- user-010: RPC calls to run later, as the user who scheduled them
+CREATE TABLE rpc_scheduled (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id TEXT NOT NULL,
+    method TEXT NOT NULL,
+    data JSONB NOT NULL,
+    execute_at TIMESTAMPTZ NOT NULL,
+    -- pending, running, done, failed or cancelled
+    state TEXT NOT NULL DEFAULT 'pending',
+    -- The rpc_logs entry written when the call was scheduled, updated once it runs or is cancelled
+    log_id UUID NOT NULL REFERENCES rpc_logs (id),
+    claimed_at TIMESTAMPTZ,
+    ran_at TIMESTAMPTZ,
+    last_error TEXT,
+    cancelled_by TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX rpc_scheduled_due_idx ON rpc_scheduled (execute_at) WHERE state IN ('pending', 'running');
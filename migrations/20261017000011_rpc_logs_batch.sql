@@ -0,0 +1,4 @@
+-- user-011: methods run through /batch share a batch ID
+ALTER TABLE rpc_logs ADD COLUMN batch_id UUID;
+
+CREATE INDEX rpc_logs_batch_id_idx ON rpc_logs (batch_id) WHERE batch_id IS NOT NULL;
@@ -0,0 +1,6 @@
+-- user-012: users locked out of RPC after repeatedly hitting the rate limit, shared by all instances
+CREATE TABLE rpc_locks (
+    user_id TEXT PRIMARY KEY,
+    locked_until TIMESTAMPTZ NOT NULL,
+    reason TEXT NOT NULL
+);
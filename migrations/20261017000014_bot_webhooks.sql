@@ -0,0 +1,27 @@
+-- user-014: webhooks bot owners receive review outcomes on, and the outbox of deliveries to them
+CREATE TABLE bot_webhooks (
+    -- One webhook per bot, ``ON CONFLICT (bot_id)`` relies on this
+    bot_id TEXT PRIMARY KEY REFERENCES bots (bot_id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE bot_webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    bot_id TEXT NOT NULL REFERENCES bots (bot_id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    -- pending, sent, dead or cancelled
+    state TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    response_status INTEGER,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX bot_webhook_deliveries_pending_idx ON bot_webhook_deliveries (next_attempt_at) WHERE state = 'pending';
+CREATE INDEX bot_webhook_deliveries_bot_id_idx ON bot_webhook_deliveries (bot_id, created_at);
@@ -0,0 +1,3 @@
+-- user-018: /logs pages through rpc_logs by (created_at, id), newest first
+CREATE INDEX rpc_logs_created_at_idx ON rpc_logs (created_at DESC, id DESC);
+CREATE INDEX rpc_logs_user_id_idx ON rpc_logs (user_id, created_at DESC);
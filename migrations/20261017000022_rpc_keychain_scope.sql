@@ -0,0 +1,2 @@
+-- user-022: the bots, teams and argument limits an identity is bound to, see ``KeychainScope``
+ALTER TABLE rpc_keychain ADD COLUMN scope JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,16 @@
+-- user-023: requests are signed with a key derived from ``rpc.signing_pepper`` and the key ID, the key is never stored
+--
+-- Identities only live for a few minutes, so existing ones are dropped instead of being given a key ID
+DELETE FROM rpc_keychain;
+ALTER TABLE rpc_keychain ADD COLUMN key_id TEXT NOT NULL UNIQUE;
+
+-- Nonces of signed requests, so a request cannot be replayed against any instance
+CREATE TABLE rpc_nonces (
+    key_id TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- ``ON CONFLICT (key_id, nonce)`` relies on this
+    PRIMARY KEY (key_id, nonce)
+);
+
+CREATE INDEX rpc_nonces_created_at_idx ON rpc_nonces (created_at);
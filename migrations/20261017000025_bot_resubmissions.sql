@@ -0,0 +1,15 @@
+-- user-025: every time a denied bot was moved back into the queue, with the denial it was moved back from
+CREATE TABLE bot_resubmissions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    bot_id TEXT NOT NULL REFERENCES bots (bot_id) ON DELETE CASCADE,
+    requested_by TEXT NOT NULL,
+    -- owner (/resubmit) or staff (BotRequeue)
+    source TEXT NOT NULL,
+    note TEXT NOT NULL,
+    denied_by TEXT,
+    denial_reason TEXT,
+    denied_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX bot_resubmissions_bot_id_idx ON bot_resubmissions (bot_id, created_at DESC);
@@ -4,8 +4,7 @@ use std::num::NonZeroU64;
 use crate::checks;
 use crate::Context;
 use crate::Error;
-use crate::rpc::server::KeychainData;
-use moka::future::ConcurrentCacheExt;
+use crate::rpc::keychain::{KeychainData, KeychainScope};
 use poise::serenity_prelude::ButtonStyle;
 use poise::serenity_prelude::CacheHttp;
 use poise::serenity_prelude::CreateActionRow;
@@ -32,7 +31,11 @@ pub async fn rpcidentify(
     ctx: Context<'_>,
     #[description = "Purpose"] purpose: String,
     #[description = "Which methods to enable"] methods: Option<String>,
-    #[description = "Maximum Uses"] max_uses: Option<u8>
+    #[description = "Maximum Uses"] max_uses: Option<u8>,
+    #[description = "Only allow acting on these bots (comma separated IDs)"] bots: Option<String>,
+    #[description = "Only allow acting on these teams and their bots (comma separated IDs)"] teams: Option<String>,
+    #[description = "Largest vote count that can be set"] max_count: Option<i64>,
+    #[description = "Largest time period (in hours) that can be given"] max_hours: Option<i64>,
 ) -> Result<(), Error> {
     // Parse methods
     let methods = methods.unwrap_or("BotClaim,BotUnclaim,BotApprove,BotDeny".into());
@@ -66,6 +69,43 @@ pub async fn rpcidentify(
         );
     }
 
+    // Parse the scope
+    let split_ids = |ids: Option<String>| -> Vec<String> {
+        ids.unwrap_or_default()
+            .replace(' ', ",")
+            .split(',')
+            .filter(|id| !id.is_empty())
+            .map(|id| id.to_string())
+            .collect()
+    };
+
+    let scope = KeychainScope {
+        bot_ids: split_ids(bots),
+        team_ids: split_ids(teams),
+        max_count,
+        max_time_period_hours: max_hours,
+    };
+
+    for bot_id in scope.bot_ids.iter() {
+        if bot_id.parse::<NonZeroU64>().is_err() {
+            return Err(
+                format!(
+                    "Invalid bot ID: {}", bot_id
+                ).into()
+            );
+        }
+    }
+
+    for team_id in scope.team_ids.iter() {
+        if team_id.parse::<sqlx::types::Uuid>().is_err() {
+            return Err(
+                format!(
+                    "Invalid team ID: {}", team_id
+                ).into()
+            );
+        }
+    }
+
     let warn_embed = {
         CreateEmbed::new()
         .title(":warning: Warning")
@@ -78,10 +118,12 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
 
 **Given Reason:** {}
 **Allowed Methods:** {:?}
+**Scope:** {}
 **Maximum Uses:** {}
             ", 
             purpose,
             allowed_methods,
+            scope.describe(),
             max_uses
         ))
         .color(0xFF0000)
@@ -118,13 +160,11 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
             item.defer_ephemeral(&ctx.discord()).await?;
 
             // Check number of current RPC sessions
-            crate::rpc::server::RPC_KEYCHAIN.sync();
-            
-            if crate::rpc::server::RPC_KEYCHAIN.entry_count() > 10 {
+            if KeychainData::active_count(&ctx.data().pool).await? > 10 {
                 item.create_followup(
                     &ctx.discord(),
                     serenity::CreateInteractionResponseFollowup::default()
-                    .content("Kittycat Security Patrol: Too many RPC sessions are currently active, please try again later.")
+                    .content("Kittycat Security Patrol: Too many RPC sessions are currently active, please try again later or revoke unused ones with `/rpcsessions revoke`.")
                 )
                 .await?;
 
@@ -132,7 +172,9 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
             }
 
             // Create a RPC identity
-            let rpc_identity = "Bluejay$V1:".to_string()+&crate::impls::crypto::gen_random(65535);
+            // The identity carries its key ID and the signing key derived from it, only the key ID is stored
+            let key_id = crate::impls::crypto::gen_random(32);
+            let rpc_identity = crate::rpc::signing::identity(&key_id);
 
             sqlx::query!(
                 "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
@@ -142,6 +184,7 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
                     "reason": purpose,
                     "allowed_methods": allowed_methods,
                     "max_uses": max_uses,
+                    "scope": scope,
                 })
             )
             .execute(&ctx.data().pool)
@@ -154,6 +197,20 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
             .execute(&ctx.data().pool)
             .await?;
 
+            // save the identity
+            KeychainData {
+                user_id: ctx.author().id.to_string(),
+                allowed_methods: allowed_methods.into_iter().map(|x| x.to_string()).collect(),
+                max_uses: max_uses.into(),
+                used: 0,
+                reason: purpose,
+                expires_at: chrono::Utc::now() + chrono::Duration::from_std(crate::rpc::keychain::KEYCHAIN_TTL)?,
+                scope,
+                key_id,
+            }
+            .insert(&ctx.data().pool, &rpc_identity)
+            .await?;
+
             let filehash = crate::impls::crypto::gen_random(12);
 
             item.create_followup(
@@ -168,15 +225,6 @@ To continue, please click the `Unlock` button OR instead, (PREFERRED) just use b
                 )
             )
             .await?;
-
-            // save the identity
-            crate::rpc::server::RPC_KEYCHAIN.insert(rpc_identity, KeychainData {
-                user_id: ctx.author().id.to_string(),
-                allowed_methods: allowed_methods.into_iter().map(|x| x.to_string()).collect(),
-                max_uses,
-                used: 0,
-                reason: purpose,
-            }).await;
         }
     }
 
@@ -197,8 +245,11 @@ pub async fn rpclock(
     // Parse the file
     let file = String::from_utf8(file)?;
 
-    // Remove identity from moka
-    crate::rpc::server::RPC_KEYCHAIN.remove(&file).await;
+    // Remove identity from the keychain
+    if !KeychainData::remove(&ctx.data().pool, &file).await? {
+        ctx.say("This RPC identity does not exist or has already expired").await?;
+        return Ok(());
+    }
 
     ctx.say("RPC has been locked").await?;
 
@@ -1,5 +1,10 @@
+use crate::impls::notifications::Notification;
+use crate::impls::resubmissions::{self, ResubmitSource};
+use crate::impls::webhooks;
+use crate::rpc::events::RPCEvent;
 use crate::{checks, config};
-use poise::serenity_prelude::{CacheHttp, GuildId, RoleId};
+use poise::serenity_prelude::{CacheHttp, CreateEmbed, CreateMessage, GuildId, RoleId};
+use poise::CreateReply;
 
 type Error = crate::Error;
 type Context<'a> = crate::Context<'a>;
@@ -117,3 +122,228 @@ pub async fn getbotroles(ctx: Context<'_>) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Errors if the author does not own (or is not on the team owning) the bot
+async fn check_bot_owner(ctx: Context<'_>, bot_id: &str) -> Result<(), Error> {
+    let members = crate::impls::utils::get_bot_members(bot_id, &ctx.data().pool).await?;
+
+    if !members.contains(&ctx.author().id.to_string()) {
+        return Err("You are not the owner/additional owner of this bot".into());
+    }
+
+    Ok(())
+}
+
+/// Manage the webhook your bot's review outcomes are sent to
+#[poise::command(
+    category = "Bot Owner",
+    slash_command,
+    subcommands("webhook_set", "webhook_remove", "webhook_deliveries")
+)]
+pub async fn webhook(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Sets the webhook URL (and secret) of a bot
+///
+/// Each delivery is signed with HMAC-SHA256 over ``<X-Arcadia-Timestamp>.<body>`` using the secret
+#[poise::command(category = "Bot Owner", slash_command, ephemeral, rename = "set")]
+pub async fn webhook_set(
+    ctx: Context<'_>,
+    #[description = "The bot ID"] bot_id: String,
+    #[description = "The URL to POST events to, must be HTTPS and public"] url: String,
+    #[description = "The signing secret, one is generated if not set"] secret: Option<String>,
+) -> Result<(), Error> {
+    check_bot_owner(ctx, &bot_id).await?;
+
+    webhooks::check_url(&url).await?;
+
+    let secret = secret.unwrap_or_else(|| crate::impls::crypto::gen_random(64));
+
+    if secret.len() < 16 {
+        return Err("Webhook secrets must be at least 16 characters long".into());
+    }
+
+    sqlx::query!(
+        "INSERT INTO bot_webhooks (bot_id, url, secret, created_by) VALUES ($1, $2, $3, $4) ON CONFLICT (bot_id) DO UPDATE SET url = EXCLUDED.url, secret = EXCLUDED.secret, created_by = EXCLUDED.created_by",
+        bot_id,
+        url,
+        secret,
+        ctx.author().id.to_string()
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+
+    ctx.say(format!(
+        "Webhook set! Events for <@{}> will be sent to `{}`\n\n**Secret:** ||{}||\n\nVerify the `{}` header on each request to make sure it came from us",
+        bot_id,
+        url,
+        secret,
+        crate::impls::webhooks::SIGNATURE_HEADER
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the webhook of a bot, pending deliveries are dropped
+#[poise::command(category = "Bot Owner", slash_command, ephemeral, rename = "remove")]
+pub async fn webhook_remove(
+    ctx: Context<'_>,
+    #[description = "The bot ID"] bot_id: String,
+) -> Result<(), Error> {
+    check_bot_owner(ctx, &bot_id).await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!("DELETE FROM bot_webhooks WHERE bot_id = $1", bot_id)
+        .execute(&mut tx)
+        .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("This bot does not have a webhook".into());
+    }
+
+    sqlx::query!(
+        "UPDATE bot_webhook_deliveries SET state = 'cancelled' WHERE bot_id = $1 AND state = 'pending'",
+        bot_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.say("Webhook removed").await?;
+
+    Ok(())
+}
+
+/// Shows the most recent webhook deliveries of a bot
+#[poise::command(
+    category = "Bot Owner",
+    slash_command,
+    ephemeral,
+    rename = "deliveries"
+)]
+pub async fn webhook_deliveries(
+    ctx: Context<'_>,
+    #[description = "The bot ID"] bot_id: String,
+) -> Result<(), Error> {
+    check_bot_owner(ctx, &bot_id).await?;
+
+    let deliveries = sqlx::query!(
+        "SELECT id, event, state, attempts, response_status, last_error, created_at FROM bot_webhook_deliveries WHERE bot_id = $1 ORDER BY created_at DESC LIMIT 10",
+        bot_id
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if deliveries.is_empty() {
+        ctx.say("No webhook deliveries yet").await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Recent Webhook Deliveries")
+        .color(0x00ff00);
+
+    for d in deliveries {
+        let status = match d.response_status {
+            Some(status) => status.to_string(),
+            None => "no response".to_string(),
+        };
+
+        embed = embed.field(
+            format!("{} ({})", d.event, d.id),
+            format!(
+                "**State:** {} after {} attempt(s), {}\n**Created:** <t:{}:R>{}",
+                d.state,
+                d.attempts,
+                status,
+                d.created_at.timestamp(),
+                d.last_error
+                    .map(|e| format!("\n**Last Error:** {}", e))
+                    .unwrap_or_default()
+            ),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Resubmits a denied bot to the queue once you've fixed the reason it was denied
+#[poise::command(category = "Bot Owner", slash_command, ephemeral)]
+pub async fn resubmit(
+    ctx: Context<'_>,
+    #[description = "The bot ID"] bot_id: String,
+    #[description = "What you changed since the bot was denied"] changes: String,
+) -> Result<(), Error> {
+    check_bot_owner(ctx, &bot_id).await?;
+
+    let changes_len = changes.chars().count();
+
+    if !(10..=1024).contains(&changes_len) {
+        return Err("Please describe your changes in 10 to 1024 characters".into());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let requeued = resubmissions::requeue(
+        &mut tx,
+        &bot_id,
+        &ctx.author().id.to_string(),
+        ResubmitSource::Owner,
+        &changes,
+    )
+    .await?;
+
+    let embed = CreateEmbed::default()
+        .title("Bot Resubmitted!")
+        .url(format!("{}/bots/{}", config::CONFIG.frontend_url, bot_id))
+        .description(format!(
+            "<@{}> has resubmitted <@{}> to the queue",
+            ctx.author().id,
+            bot_id
+        ))
+        .field("Changes", &changes, false)
+        .field(
+            "Previous Denial",
+            requeued
+                .denial
+                .map(|d| d.describe())
+                .unwrap_or_else(|| "*Unknown*".to_string())
+                .chars()
+                .take(1024)
+                .collect::<String>(),
+            false,
+        );
+
+    // Queue the mod log message with the resubmission so neither can happen without the other
+    Notification::new(
+        config::CONFIG.channels.mod_logs,
+        CreateMessage::default().embed(embed),
+    )
+    .enqueue(&mut tx)
+    .await?;
+
+    let event = RPCEvent::BotResubmitted {
+        bot_id: bot_id.clone(),
+        by: ctx.author().id.to_string(),
+        reason: changes,
+        by_owner: true,
+    };
+
+    webhooks::enqueue(&mut tx, &bot_id, &event).await?;
+
+    tx.commit().await?;
+
+    event.publish();
+
+    ctx.say("Your bot has been resubmitted to the queue, staff will review it again soon!")
+        .await?;
+
+    Ok(())
+}
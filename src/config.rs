@@ -1,6 +1,6 @@
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::Write, num::NonZeroU64};
+use std::{collections::HashMap, fs::File, io::Write, net::SocketAddr, num::NonZeroU64};
 
 use crate::Error;
 
@@ -65,6 +65,9 @@ pub struct Channels {
     // System channel
     pub system: NonZeroU64,
     pub uptime: NonZeroU64,
+    /// Where RPC actions needing a second approval are posted, ``mod_logs`` is used if unset
+    #[serde(default)]
+    pub rpc_approvals: Option<NonZeroU64>,
 }
 
 impl Default for Channels {
@@ -74,6 +77,100 @@ impl Default for Channels {
             mod_logs: NonZeroU64::new(911907978926493716).unwrap(),
             system: NonZeroU64::new(762958420277067786).unwrap(),
             uptime: NonZeroU64::new(1083108330442076292).unwrap(),
+            rpc_approvals: None,
+        }
+    }
+}
+
+impl Channels {
+    /// The channel RPC approvals and locks are posted to
+    pub fn rpc_approvals(&self) -> NonZeroU64 {
+        self.rpc_approvals.unwrap_or(self.mod_logs)
+    }
+}
+
+/// A token bucket, ``burst`` calls can be made at once and one call is regained every ``refill_secs``
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub refill_secs: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RPCRateLimits {
+    pub owner: RateLimit,
+    pub head: RateLimit,
+    pub admin: RateLimit,
+    pub staff: RateLimit,
+    /// Per-method limits, these apply on top of the permission tier limits
+    pub methods: HashMap<String, RateLimit>,
+    /// RPC is locked for a user who is rate limited this many times within ``strike_window_secs``
+    pub lock_after_strikes: u32,
+    pub strike_window_secs: u64,
+    pub lock_minutes: i64,
+}
+
+impl Default for RPCRateLimits {
+    fn default() -> Self {
+        Self {
+            owner: RateLimit {
+                burst: 20,
+                refill_secs: 20,
+            },
+            head: RateLimit {
+                burst: 10,
+                refill_secs: 40,
+            },
+            admin: RateLimit {
+                burst: 10,
+                refill_secs: 40,
+            },
+            staff: RateLimit {
+                burst: 5,
+                refill_secs: 80,
+            },
+            methods: HashMap::from([(
+                "BotVoteResetAll".to_string(),
+                RateLimit {
+                    burst: 1,
+                    refill_secs: 60 * 60,
+                },
+            )]),
+            lock_after_strikes: 5,
+            strike_window_secs: 10 * 60,
+            lock_minutes: 30,
+        }
+    }
+}
+
+/// Every key is optional, missing keys take their value from ``RPCServer::default``
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RPCServer {
+    /// Address the RPC server listens on
+    pub bind: SocketAddr,
+    /// Origins the staff panel may call the RPC server from, ``*`` allows any origin
+    pub allowed_origins: Vec<String>,
+    /// The largest request body accepted, in bytes
+    pub max_body_bytes: usize,
+    /// PEM certificate chain, the server is served over TLS (with rustls) if this and ``tls_key`` are set
+    pub tls_cert: Option<String>,
+    /// PEM private key of ``tls_cert``
+    pub tls_key: Option<String>,
+    /// Secret the signing keys of RPC identities are derived from, at least 32 characters.
+    /// Changing this invalidates every RPC identity
+    pub signing_pepper: String,
+}
+
+impl Default for RPCServer {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 3010)),
+            allowed_origins: vec!["*".to_string()],
+            max_body_bytes: 2 * 1024 * 1024,
+            tls_cert: None,
+            tls_key: None,
+            signing_pepper: String::new(),
         }
     }
 }
@@ -93,6 +190,21 @@ pub struct Config {
     pub github_username: String,
     pub github_repo: String,
     pub optional_vercel_deploy_hook: Option<String>,
+    /// RPC methods that need a second eligible user to approve them before they run
+    #[serde(default = "default_quorum_methods")]
+    pub rpc_quorum_methods: Vec<String>,
+    #[serde(default)]
+    pub rpc_ratelimits: RPCRateLimits,
+    #[serde(default)]
+    pub rpc: RPCServer,
+}
+
+fn default_quorum_methods() -> Vec<String> {
+    vec![
+        "BotVoteResetAll".to_string(),
+        "BotCertifyAdd".to_string(),
+        "BotVoteCountSet".to_string(),
+    ]
 }
 
 impl Default for Config {
@@ -113,6 +225,9 @@ impl Default for Config {
             github_username: String::from(""),
             github_repo: String::from("InfinityBotList/Infinity-Next"),
             optional_vercel_deploy_hook: None,
+            rpc_quorum_methods: default_quorum_methods(),
+            rpc_ratelimits: RPCRateLimits::default(),
+            rpc: RPCServer::default(),
         }
     }
 }
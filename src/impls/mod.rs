@@ -1,3 +1,6 @@
 pub mod cache;
 pub mod crypto;
+pub mod notifications;
+pub mod resubmissions;
 pub mod utils;
+pub mod webhooks;
@@ -0,0 +1,74 @@
+use std::num::NonZeroU64;
+
+use poise::serenity_prelude::CreateMessage;
+use sqlx::{Executor, Postgres};
+
+use crate::Error;
+
+/// How many times delivery of a notification is attempted before it is dead-lettered
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// The longest we will ever wait between two delivery attempts, in seconds
+pub const MAX_BACKOFF_SECS: f64 = 60.0 * 60.0;
+
+/// How long one delivery attempt may take, in seconds. A claimed row is picked up again after this
+/// if the worker that claimed it never finished (such as on a restart)
+pub const CLAIM_SECS: f64 = 5.0 * 60.0;
+
+/// Delivered rows are kept this many days (so dedup keys keep working) before they are deleted
+pub const KEEP_SENT_DAYS: i32 = 7;
+
+/// Dead-lettered rows are kept this many days for debugging before they are deleted
+pub const KEEP_DEAD_DAYS: i32 = 30;
+
+/// A message to be posted to a channel (mod logs, system channel etc.)
+///
+/// Notifications are never sent inline. They are written to the ``notifications`` outbox table and
+/// delivered by the ``notifications`` task, which retries with exponential backoff
+pub struct Notification {
+    pub channel_id: NonZeroU64,
+    pub message: CreateMessage,
+    /// Only one notification is ever queued for a given dedup key
+    pub dedup_key: Option<String>,
+}
+
+impl Notification {
+    pub fn new(channel_id: NonZeroU64, message: CreateMessage) -> Self {
+        Self {
+            channel_id,
+            message,
+            dedup_key: None,
+        }
+    }
+
+    /// Sets a dedup key so retries of the writer do not post the same message twice
+    pub fn dedup(mut self, key: impl Into<String>) -> Self {
+        self.dedup_key = Some(key.into());
+        self
+    }
+
+    /// Writes the notification to the outbox
+    ///
+    /// Pass a transaction to only deliver the notification if the transaction is committed
+    pub async fn enqueue<'c, E>(&self, executor: E) -> Result<(), Error>
+    where
+        E: Executor<'c, Database = Postgres>,
+    {
+        sqlx::query!(
+            "INSERT INTO notifications (channel_id, payload, dedup_key) VALUES ($1, $2, $3) ON CONFLICT (dedup_key) DO NOTHING",
+            self.channel_id.to_string(),
+            serde_json::to_value(&self.message)?,
+            self.dedup_key
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Returns how long to wait (in seconds) before the next delivery attempt
+pub fn backoff_secs(attempts: i32) -> f64 {
+    // 30s, 1m, 2m, 4m ... capped at MAX_BACKOFF_SECS
+    (30.0 * 2f64.powi(attempts.saturating_sub(1))).min(MAX_BACKOFF_SECS)
+}
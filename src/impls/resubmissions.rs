@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool, Postgres, Transaction};
+
+use crate::rpc::error::RPCFailure;
+use crate::Error;
+
+/// How long owners have to wait between resubmissions of the same bot
+pub const OWNER_RESUBMIT_COOLDOWN_HOURS: i32 = 24;
+
+/// Who moved a denied bot back into the queue
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ResubmitSource {
+    /// A staff member, through ``BotRequeue``
+    Staff,
+    /// An owner of the bot, through ``/resubmit``
+    Owner,
+}
+
+impl ResubmitSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResubmitSource::Staff => "staff",
+            ResubmitSource::Owner => "owner",
+        }
+    }
+}
+
+/// The most recent denial of a bot, as found in the audit logs
+pub struct Denial {
+    pub denied_by: String,
+    pub reason: String,
+    pub denied_at: DateTime<Utc>,
+}
+
+impl Denial {
+    /// A short description of the denial, for embeds
+    pub fn describe(&self) -> String {
+        format!(
+            "Denied by <@{}> <t:{}:R>: {}",
+            self.denied_by,
+            self.denied_at.timestamp(),
+            self.reason
+        )
+    }
+}
+
+/// One time a bot was moved back into the queue
+pub struct Resubmission {
+    pub requested_by: String,
+    pub source: String,
+    pub note: String,
+    /// The denial this resubmission answers, ``None`` if no denial was found in the audit logs
+    pub denied_by: Option<String>,
+    pub denial_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What ``requeue`` changed
+pub struct Requeued {
+    pub denial: Option<Denial>,
+    pub bots: PgQueryResult,
+    pub resubmissions: PgQueryResult,
+    /// The ``bot_resubmissions`` row recorded for this requeue
+    pub resubmission_id: Uuid,
+}
+
+/// Finds the most recent denial of a bot, through either ``BotDeny`` or ``BotBulkDeny``
+pub async fn last_denial(
+    tx: &mut Transaction<'_, Postgres>,
+    bot_id: &str,
+) -> Result<Option<Denial>, Error> {
+    let rec = sqlx::query!(
+        "SELECT user_id AS \"denied_by!\", reason AS \"reason!\", created_at AS \"denied_at!\" FROM (
+            SELECT user_id, data -> 'BotDeny' ->> 'reason' AS reason, created_at FROM rpc_logs
+            WHERE method = 'BotDeny' AND state = 'success' AND data -> 'BotDeny' ->> 'bot_id' = $1
+            UNION ALL
+            SELECT user_id, data ->> 'reason' AS reason, created_at FROM staff_general_logs
+            WHERE action = 'bulk_denied' AND data ->> 'bot_id' = $1
+        ) denials ORDER BY created_at DESC LIMIT 1",
+        bot_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(rec.map(|r| Denial {
+        denied_by: r.denied_by,
+        reason: r.reason,
+        denied_at: r.denied_at,
+    }))
+}
+
+/// Moves a denied bot back to pending, saving a link to the denial it answers
+pub async fn requeue(
+    tx: &mut Transaction<'_, Postgres>,
+    bot_id: &str,
+    requested_by: &str,
+    source: ResubmitSource,
+    note: &str,
+) -> Result<Requeued, Error> {
+    let bot = sqlx::query!("SELECT type FROM bots WHERE bot_id = $1 FOR UPDATE", bot_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    match bot {
+        Some(bot) if bot.r#type == "denied" => {}
+        Some(_) => {
+            return Err(RPCFailure::invalid_state("Only denied bots can be resubmitted").into())
+        }
+        None => return Err(RPCFailure::not_found("Bot does not exist").into()),
+    }
+
+    if source == ResubmitSource::Owner {
+        let recent = sqlx::query!(
+            "SELECT COUNT(*) FROM bot_resubmissions WHERE bot_id = $1 AND source = 'owner' AND created_at > NOW() - make_interval(hours => $2)",
+            bot_id,
+            OWNER_RESUBMIT_COOLDOWN_HOURS
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if recent.count.unwrap_or_default() > 0 {
+            return Err(RPCFailure::invalid_state(format!(
+                "This bot was already resubmitted in the last {} hours",
+                OWNER_RESUBMIT_COOLDOWN_HOURS
+            ))
+            .into());
+        }
+    }
+
+    let denial = last_denial(tx, bot_id).await?;
+
+    let bots = sqlx::query!(
+        "UPDATE bots SET type = 'pending', claimed_by = NULL WHERE bot_id = $1",
+        bot_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let resubmission_id = sqlx::query!("SELECT gen_random_uuid() AS \"id!\"")
+        .fetch_one(&mut *tx)
+        .await?
+        .id;
+
+    let resubmissions = sqlx::query!(
+        "INSERT INTO bot_resubmissions (id, bot_id, requested_by, source, note, denied_by, denial_reason, denied_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        resubmission_id,
+        bot_id,
+        requested_by,
+        source.as_str(),
+        note,
+        denial.as_ref().map(|d| d.denied_by.as_str()),
+        denial.as_ref().map(|d| d.reason.as_str()),
+        denial.as_ref().map(|d| d.denied_at)
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(Requeued {
+        denial,
+        bots,
+        resubmissions,
+        resubmission_id,
+    })
+}
+
+/// Returns the resubmissions of a bot, newest first
+pub async fn history(pool: &PgPool, bot_id: &str) -> Result<Vec<Resubmission>, Error> {
+    let recs = sqlx::query!(
+        "SELECT requested_by, source, note, denied_by, denial_reason, created_at FROM bot_resubmissions WHERE bot_id = $1 ORDER BY created_at DESC",
+        bot_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| Resubmission {
+            requested_by: r.requested_by,
+            source: r.source,
+            note: r.note,
+            denied_by: r.denied_by,
+            denial_reason: r.denial_reason,
+            created_at: r.created_at,
+        })
+        .collect())
+}
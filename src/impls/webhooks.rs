@@ -0,0 +1,197 @@
+use std::net::{IpAddr, SocketAddr};
+
+use ring::hmac;
+use sqlx::{Executor, Postgres};
+
+use crate::rpc::events::RPCEvent;
+use crate::Error;
+
+/// How many times delivery of a webhook is attempted before it is dead-lettered
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// Header containing the unix timestamp the delivery attempt was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Arcadia-Timestamp";
+
+/// Header containing the HMAC-SHA256 signature of the delivery, as ``sha256=<hex>``
+pub const SIGNATURE_HEADER: &str = "X-Arcadia-Signature";
+
+/// Header containing the event type, the same as the ``type`` field of the body
+pub const EVENT_HEADER: &str = "X-Arcadia-Event";
+
+/// Header containing the delivery ID, this stays the same across retries
+pub const DELIVERY_HEADER: &str = "X-Arcadia-Delivery";
+
+/// Returns the bot whose owners should receive this event on their webhook, if any
+///
+/// Only review outcomes are sent, everything else stays internal to the staff panel
+pub fn owner_event(event: &RPCEvent) -> Option<&str> {
+    match event {
+        RPCEvent::BotClaimed { bot_id, .. }
+        | RPCEvent::BotApproved { bot_id, .. }
+        | RPCEvent::BotDenied { bot_id, .. }
+        | RPCEvent::BotUnverified { bot_id, .. }
+        | RPCEvent::BotResubmitted { bot_id, .. } => Some(bot_id.as_str()),
+        RPCEvent::PremiumChanged {
+            bot_id,
+            premium: false,
+            ..
+        } => Some(bot_id.as_str()),
+        _ => None,
+    }
+}
+
+/// Queues an event for delivery to the webhook of the bot, does nothing if the bot has no webhook
+///
+/// Pass a transaction to only deliver the event if the transaction is committed
+pub async fn enqueue<'c, E>(executor: E, bot_id: &str, event: &RPCEvent) -> Result<(), Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query!(
+        "INSERT INTO bot_webhook_deliveries (bot_id, event, payload) SELECT bot_id, $2, $3 FROM bot_webhooks WHERE bot_id = $1",
+        bot_id,
+        event.name(),
+        serde_json::to_value(event)?
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Signs a webhook body, owners verify this by computing the same HMAC over ``<timestamp>.<body>``
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, format!("{}.{}", timestamp, body).as_bytes());
+
+    format!("sha256={}", data_encoding::HEXLOWER.encode(tag.as_ref()))
+}
+
+/// Whether an address is publicly routable, webhooks are never sent anywhere else
+///
+/// This keeps owners from pointing their webhook at loopback, the private network or cloud metadata endpoints
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                // IETF protocol assignments
+                || (a == 192 && b == 0 && c == 0)
+                // Benchmarking
+                || (a == 198 && (18..20).contains(&b))
+                // Reserved
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+
+            let segments = ip.segments();
+
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local
+                || (segments[0] & 0xfe00) == 0xfc00
+                // Link local
+                || (segments[0] & 0xffc0) == 0xfe80
+                // Documentation
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                // NAT64, this could reach any IPv4 address
+                || (segments[0] == 0x64 && segments[1] == 0xff9b))
+        }
+    }
+}
+
+/// Resolves a webhook host, failing if any address it resolves to is not publicly routable
+pub async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+        .collect::<Vec<_>>();
+
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve to any address", host).into());
+    }
+
+    if let Some(addr) = addrs.iter().find(|a| !is_public_ip(a.ip())) {
+        return Err(format!(
+            "Webhooks cannot be sent to {} as it is not a public address",
+            addr.ip()
+        )
+        .into());
+    }
+
+    Ok(addrs)
+}
+
+/// Parses a webhook URL, checking that it uses HTTPS and only points to public addresses
+pub async fn check_url(url: &str) -> Result<reqwest::Url, Error> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+
+    if parsed.scheme() != "https" {
+        return Err("Webhook URLs must use HTTPS".into());
+    }
+
+    let host = match parsed.host_str() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return Err("Webhook URLs must have a host".into()),
+    };
+
+    resolve_public(host, parsed.port_or_known_default().unwrap_or(443)).await?;
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn rejects_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!public(ip), "{} should not be public", ip);
+        }
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        for ip in [
+            "1.1.1.1",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(public(ip), "{} should be public", ip);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use log::{error, info};
 use poise::serenity_prelude::{
-    self as serenity, ChannelId, CreateEmbed, CreateMessage, FullEvent, GuildId, RoleId, Timestamp,
+    self as serenity, CreateEmbed, CreateMessage, FullEvent, GuildId, RoleId, Timestamp,
 };
 use sqlx::postgres::PgPoolOptions;
 
@@ -110,6 +110,18 @@ async fn event_listener(event: &FullEvent, user_data: &Data) -> Result<(), Error
             ctx: _,
         } => {
             info!("Interaction received: {:?}", interaction.id());
+
+            // Approve/Reject buttons of RPC actions needing a quorum
+            if let serenity::Interaction::Component(component) = interaction {
+                if component.data.custom_id.starts_with("rpcq:") {
+                    rpc::quorum::handle_interaction(
+                        &user_data.pool,
+                        &user_data.cache_http,
+                        component,
+                    )
+                    .await?;
+                }
+            }
         }
         FullEvent::CacheReady { ctx: _, guilds } => {
             info!("Cache ready with {} guilds", guilds.len());
@@ -157,9 +169,8 @@ async fn event_listener(event: &FullEvent, user_data: &Data) -> Result<(), Error
                 }
 
                 // Send member join message
-                ChannelId(config::CONFIG.channels.system)
-                .send_message(
-                    &ctx,
+                impls::notifications::Notification::new(
+                    config::CONFIG.channels.system,
                     CreateMessage::new()
                     .embed(
                         CreateEmbed::default()
@@ -176,8 +187,20 @@ async fn event_listener(event: &FullEvent, user_data: &Data) -> Result<(), Error
                         .timestamp(Timestamp::now())
                     )
                 )
+                .dedup(format!(
+                    "member_join:{}:{}",
+                    new_member.user.id,
+                    new_member.joined_at.map(|t| t.unix_timestamp()).unwrap_or_default()
+                ))
+                .enqueue(&user_data.pool)
                 .await?;
 
+                rpc::events::RPCEvent::BotJoined {
+                    bot_id: new_member.user.id.to_string(),
+                    name: new_member.user.name.clone(),
+                }
+                .publish();
+
                 // Give bot role
                 ctx.http
                     .add_member_role(
@@ -191,9 +214,8 @@ async fn event_listener(event: &FullEvent, user_data: &Data) -> Result<(), Error
 
             if new_member.guild_id.0 == config::CONFIG.servers.main && !new_member.user.bot {
                 // Send member join message
-                ChannelId(config::CONFIG.channels.system)
-                .send_message(
-                    &ctx,
+                impls::notifications::Notification::new(
+                    config::CONFIG.channels.system,
                     CreateMessage::new()
                     .embed(
                         CreateEmbed::default()
@@ -210,6 +232,12 @@ async fn event_listener(event: &FullEvent, user_data: &Data) -> Result<(), Error
                         .timestamp(Timestamp::now())
                     )
                 )
+                .dedup(format!(
+                    "member_join:{}:{}",
+                    new_member.user.id,
+                    new_member.joined_at.map(|t| t.unix_timestamp()).unwrap_or_default()
+                ))
+                .enqueue(&user_data.pool)
                 .await?;
             }
         }
@@ -267,7 +295,14 @@ async fn main() {
                 admin::uninvitedbots(),
                 stats::stats(),
                 botowners::getbotroles(),
+                botowners::webhook(),
+                botowners::resubmit(),
                 rpc::command::rpc(),
+                rpc::command::rpcundo(),
+                rpc::command::rpcscheduled(),
+                rpc::command::rpcsessions(),
+                rpc::command::rpclogs(),
+                rpc::command::audit(),
                 test::modaltest(),
             ],
             /// This code is run before every command
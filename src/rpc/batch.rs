@@ -0,0 +1,170 @@
+use sqlx::types::Uuid;
+
+use super::core::{self, RPCHandle, RPCMethod, RPCSuccess};
+use super::effects::RPCEffects;
+use super::error::{RPCErrorCode, RPCFailure};
+use crate::Error;
+
+/// The most methods a single batch may contain
+pub const MAX_BATCH: usize = 25;
+
+/// Checks that a batch has at least one and at most ``MAX_BATCH`` methods
+pub fn check_size(methods: &[RPCMethod]) -> Result<(), Error> {
+    if methods.is_empty() {
+        return Err(RPCFailure::invalid_argument("Batch must contain at least one method").into());
+    }
+
+    if methods.len() > MAX_BATCH {
+        return Err(RPCFailure::invalid_argument(format!(
+            "Batch can contain at most {} methods",
+            MAX_BATCH
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Runs several methods as one call, returning the outcome of each method in order
+///
+/// The whole batch shares one ``rpc_logs`` batch ID and each method in it takes its own rate limit token.
+/// If ``atomic`` is set, all methods run in a single transaction and either all or none are applied
+pub async fn handle_batch(
+    methods: &[RPCMethod],
+    state: RPCHandle,
+    atomic: bool,
+) -> Result<Vec<Result<RPCSuccess, Error>>, Error> {
+    check_size(methods)?;
+
+    if atomic && !state.dry_run {
+        if let Some(method) = methods.iter().find(|m| m.needs_quorum()) {
+            return Err(RPCFailure::invalid_argument(format!(
+                "`{}` needs a second approval and cannot be part of an atomic batch",
+                method
+            ))
+            .into());
+        }
+    }
+
+    core::precheck(&state, methods).await?;
+
+    let batch_id = sqlx::query!("SELECT gen_random_uuid() AS \"id!\"")
+        .fetch_one(&state.pool)
+        .await?
+        .id;
+
+    if !atomic {
+        let mut results = Vec::new();
+
+        for method in methods {
+            results.push(run_one(method, &state, batch_id).await);
+        }
+
+        return Ok(results);
+    }
+
+    // Dry runs are never logged, nothing is changed by them
+    let mut log_ids = Vec::new();
+
+    if !state.dry_run {
+        for method in methods {
+            log_ids.push(method.log(&state, Some(batch_id)).await?);
+        }
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let mut outcomes = Vec::new();
+
+    for (i, method) in methods.iter().enumerate() {
+        let mut effects = RPCEffects::default();
+
+        // The after-image is taken right after the method, before later methods change the same rows
+        let res = match method.handle_method(&state, &mut tx, &mut effects).await {
+            Ok(resp) if !state.dry_run => core::record_success(&mut tx, log_ids[i], &mut effects)
+                .await
+                .map(|_| resp),
+            res => res,
+        };
+
+        match res {
+            Ok(resp) => outcomes.push((resp, effects)),
+            Err(e) => {
+                tx.rollback().await?;
+
+                // Everything before the failing method was rolled back, everything after never ran
+                let mut results: Vec<Result<RPCSuccess, Error>> = Vec::new();
+
+                for j in 0..methods.len() {
+                    let (state_str, result) = if j == i {
+                        (e.to_string(), None)
+                    } else {
+                        let msg = format!("Aborted as method {} of the batch failed", i + 1);
+                        (
+                            msg.clone(),
+                            Some(RPCFailure::new(RPCErrorCode::Aborted, msg)),
+                        )
+                    };
+
+                    if let Some(log_id) = log_ids.get(j) {
+                        core::set_log_state(&state.pool, *log_id, &state_str).await?;
+                    }
+
+                    if let Some(result) = result {
+                        results.push(Err(result.into()));
+                    }
+                }
+
+                results.insert(i, Err(e));
+
+                return Ok(results);
+            }
+        }
+    }
+
+    if state.dry_run {
+        tx.rollback().await?;
+
+        return Ok(outcomes
+            .into_iter()
+            .map(|(_, effects)| Ok(RPCSuccess::DryRun(effects.diff())))
+            .collect());
+    }
+
+    if let Err(e) = tx.commit().await {
+        for log_id in &log_ids {
+            core::set_log_state(&state.pool, *log_id, &e.to_string()).await?;
+        }
+
+        return Err(e.into());
+    }
+
+    let mut results = Vec::new();
+
+    for (resp, effects) in outcomes {
+        effects.dispatch(&state.cache_http).await;
+        results.push(Ok(resp));
+    }
+
+    Ok(results)
+}
+
+/// Runs one method of a non-atomic batch, each method gets its own transaction
+async fn run_one(
+    method: &RPCMethod,
+    state: &RPCHandle,
+    batch_id: Uuid,
+) -> Result<RPCSuccess, Error> {
+    if method.needs_quorum() && state.approved_by.is_none() && !state.dry_run {
+        let action_id = super::quorum::request_approval(state, method).await?;
+
+        return Ok(RPCSuccess::PendingApproval(action_id.to_string()));
+    }
+
+    if state.dry_run {
+        return method.dry_run(state).await;
+    }
+
+    let log_id = method.log(state, Some(batch_id)).await?;
+
+    method.run(state, log_id).await
+}
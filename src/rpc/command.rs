@@ -1,13 +1,22 @@
 use std::str::FromStr;
 use std::time::Duration;
 
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use futures_util::StreamExt;
+use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::{
-    ButtonStyle, CreateActionRow, CreateButton, CreateInputText, CreateInteractionResponse,
-    CreateInteractionResponseMessage, CreateQuickModal, InputTextStyle, ModalInteraction,
+    ButtonStyle, CreateActionRow, CreateAttachment, CreateButton, CreateEmbed, CreateInputText,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateQuickModal, InputTextStyle,
+    ModalInteraction,
 };
 use poise::CreateReply;
 use strum::VariantNames;
 
+use super::core::RPCPerms;
+use super::effects::RPCDiff;
+use super::keychain::KeychainData;
+use super::logs::{self, AuditSummary, ExportFormat, ExportRange, LogFilter, LogPage};
+use super::spec::{FieldType, FieldValidation, WebField};
 use crate::{Context, Error};
 
 async fn autocomplete(_ctx: Context<'_>, partial: &str) -> Vec<poise::AutocompleteChoice<String>> {
@@ -25,37 +34,64 @@ async fn autocomplete(_ctx: Context<'_>, partial: &str) -> Vec<poise::Autocomple
     choices
 }
 
-fn parse_bool(v: &str) -> Result<bool, Error> {
-    match v.to_lowercase().as_str() {
-        "true" | "t" | "y" => Ok(true),
-        "false" | "f" | "n" => Ok(false),
-        _ => Err("Invalid boolean".into()),
+/// The modal input for a field of a method
+fn input_text(field: &WebField) -> CreateInputText {
+    let style = match field.field_type {
+        FieldType::Textarea | FieldType::TextList | FieldType::TextMap => InputTextStyle::Paragraph,
+        _ => InputTextStyle::Short,
+    };
+
+    let mut input = CreateInputText::new(style, &field.label, &field.id)
+        .placeholder(&field.placeholder)
+        .required(field.required);
+
+    // Discord can enforce length limits itself, everything else is checked once the modal is submitted
+    for validation in &field.validation {
+        if let FieldValidation::Length { min, max } = validation {
+            input = input
+                .min_length((*min).min(4000) as u16)
+                .max_length((*max).min(4000) as u16);
+        }
     }
+
+    input
 }
 
-fn parse_hrs(v: &str) -> Result<i32, Error> {
-    // Split v into time and unit
-    let data = v.split(' ').collect::<Vec<&str>>();
+fn diff_summary(diff: &RPCDiff) -> String {
+    let mut summary = String::new();
 
-    if data.len() != 2 {
-        return Err(
-            "Invalid time format. Format must be WITH A SPACE BETWEEN THE NUMBER AND THE UNIT"
-                .into(),
-        );
+    for rows in &diff.rows_touched {
+        summary.push_str(&format!(
+            "- Would touch {} row(s) in `{}`\n",
+            rows.rows, rows.table
+        ));
     }
 
-    let (time, unit) = (data[0], data[1]);
+    for role in &diff.roles_changed {
+        summary.push_str(&format!(
+            "- Would {} <@&{}> for <@{}>\n",
+            if role.added { "add" } else { "remove" },
+            role.role_id,
+            role.user_id
+        ));
+    }
 
-    let time = time.parse::<i32>()?;
+    for msg in &diff.messages {
+        summary.push_str(&format!(
+            "- Would post a message in <#{}>\n",
+            msg.channel_id
+        ));
+    }
+
+    for kick in &diff.kicks {
+        summary.push_str(&format!("- Would kick <@{}>\n", kick));
+    }
 
-    match unit {
-        "years" | "year" | "y" => Ok(time * 365 * 24),
-        "months" | "month" | "mo" | "m" => Ok(time * 30 * 24),
-        "weeks" | "week" | "w" => Ok(time * 7 * 24),
-        "days" | "day" | "d" => Ok(time * 24),
-        "hours" | "hour" | "hrs" | "hr" | "h" => Ok(time),
-        _ => Err("Invalid time format. Unit must be years, months, weeks, days or hours".into()),
+    if summary.is_empty() {
+        summary.push_str("- Nothing would change");
     }
+
+    summary
 }
 
 struct GetResp {
@@ -63,14 +99,12 @@ struct GetResp {
     interaction: ModalInteraction,
 }
 
-#[poise::command(
-    prefix_command,
-    slash_command,
-    check = "crate::checks::is_staff"
-)]
+/// Runs an RPC method
+#[poise::command(prefix_command, slash_command, check = "crate::checks::is_staff")]
 pub async fn rpc(
     ctx: Context<'_>,
     #[autocomplete = "autocomplete"] method: String,
+    #[description = "Preview what would change without changing anything"] dry_run: Option<bool>,
 ) -> Result<(), Error> {
     // Creates a "blank" RPCMethod
     let variant = super::core::RPCMethod::from_str(&method)?;
@@ -113,591 +147,37 @@ pub async fn rpc(
                 return Ok(());
             }
 
-            match variant {
-                super::core::RPCMethod::BotClaim { .. } => {
-                    let qm = CreateQuickModal::new("Claim Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Force Claim [Y/N]",
-                            "fclaim",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, force) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotClaim {
-                                bot_id: bot_id.to_string(),
-                                force: parse_bool(force)?,
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotUnclaim { .. } => {
-                    let qm = CreateQuickModal::new("Unclaim Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotUnclaim {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotApprove { .. } => {
-                    let qm = CreateQuickModal::new("Approve Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotApprove {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotDeny { .. } => {
-                    let qm = CreateQuickModal::new("Deny Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotDeny {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotVoteReset { .. } => {
-                    let qm = CreateQuickModal::new("Vote Reset Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotVoteReset {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotVoteResetAll { .. } => {
-                    let qm = CreateQuickModal::new("Vote Reset All Bots").field(
-                        CreateInputText::new(InputTextStyle::Paragraph, "Reason", "reason"),
-                    );
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let reason = &inputs[0];
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotVoteResetAll {
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotUnverify { .. } => {
-                    let qm = CreateQuickModal::new("Unverify Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(
-                            CreateInputText::new(InputTextStyle::Paragraph, "Reason", "reason")
-                                .placeholder("You must give proof"),
-                        );
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotUnverify {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotPremiumAdd { .. } => {
-                    let qm = CreateQuickModal::new("Add Bot To Premium")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(
-                            CreateInputText::new(InputTextStyle::Paragraph, "Reason", "reason")
-                                .placeholder("You must give proof"),
-                        )
-                        .field(
-                            CreateInputText::new(
-                                InputTextStyle::Short,
-                                "Time Period",
-                                "time_period",
+            // The modal is built from the spec of the method, one input per field
+            let spec = variant.spec();
+
+            let mut qm = CreateQuickModal::new(spec.label);
+
+            for field in &spec.fields {
+                qm = qm.field(input_text(field));
+            }
+
+            if let Some(resp) = m.quick_modal(discord, qm).await? {
+                match variant.from_inputs(&resp.inputs) {
+                    Ok(method) => GetResp {
+                        method,
+                        interaction: resp.interaction,
+                    },
+                    Err(e) => {
+                        resp.interaction
+                            .create_response(
+                                ctx,
+                                CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::default()
+                                        .content(format!("**{}**", e)),
+                                ),
                             )
-                            .placeholder(
-                                "Format: INTEGER UNIT, e.g. 1 day, 2 weeks, 3 months, 4 years",
-                            ),
-                        );
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason, time_period_str) =
-                            (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotPremiumAdd {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                                time_period_hours: match parse_hrs(time_period_str) {
-                                    Ok(hrs) => hrs,
-                                    Err(e) => {
-                                        resp.interaction
-                                            .create_response(
-                                                ctx,
-                                                CreateInteractionResponse::Message(
-                                                    CreateInteractionResponseMessage::default()
-                                                        .content(format!("**{}**", e)),
-                                                ),
-                                            )
-                                            .await?;
-
-                                        return Ok(());
-                                    }
-                                },
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotPremiumRemove { .. } => {
-                    let qm = CreateQuickModal::new("Remove Bot From Premium")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(
-                            CreateInputText::new(InputTextStyle::Paragraph, "Reason", "reason")
-                                .placeholder("You must give proof"),
-                        );
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotPremiumRemove {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotVoteBanAdd { .. } => {
-                    let qm = CreateQuickModal::new("Vote Ban Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotVoteBanAdd {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotVoteBanRemove { .. } => {
-                    let qm = CreateQuickModal::new("Vote Ban Remove Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotVoteBanRemove {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotForceRemove { .. } => {
-                    let qm = CreateQuickModal::new("Force Remove Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ))
-                        .field(
-                            CreateInputText::new(InputTextStyle::Short, "Kick?", "kick")
-                                .placeholder("T/F"),
-                        );
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason, kick) = (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotForceRemove {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                                kick: match parse_bool(kick) {
-                                    Ok(b) => b,
-                                    Err(e) => {
-                                        resp.interaction
-                                            .create_response(
-                                                ctx,
-                                                CreateInteractionResponse::Message(
-                                                    CreateInteractionResponseMessage::default()
-                                                        .content(format!(
-                                                            "**Error parsing `kick`: {}**",
-                                                            e
-                                                        )),
-                                                ),
-                                            )
-                                            .await?;
-
-                                        return Ok(());
-                                    }
-                                },
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotCertifyAdd { .. } => {
-                    let qm = CreateQuickModal::new("Certify Bot (not recommended)")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotCertifyAdd {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotCertifyRemove { .. } => {
-                    let qm = CreateQuickModal::new("Uncertify Bot")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason) = (&inputs[0], &inputs[1]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotCertifyRemove {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotVoteCountSet { .. } => {
-                    let qm = CreateQuickModal::new("Set Bot Vote Count")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Count",
-                            "count",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason, count_str) = (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotVoteCountSet {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                                count: match count_str.parse::<i32>() {
-                                    Ok(c) => c,
-                                    Err(e) => {
-                                        resp.interaction
-                                            .create_response(
-                                                ctx,
-                                                CreateInteractionResponse::Message(
-                                                    CreateInteractionResponseMessage::default()
-                                                        .content(format!(
-                                                            "**Error parsing `count`: {}**",
-                                                            e
-                                                        )),
-                                                ),
-                                            )
-                                            .await?;
-
-                                        return Ok(());
-                                    }
-                                },
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotTransferOwnershipUser { .. } => {
-                    let qm = CreateQuickModal::new("Transfer Bot Ownership")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "New Owner ID",
-                            "new_owner",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason, new_owner) = (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotTransferOwnershipUser {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                                new_owner: new_owner.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::BotTransferOwnershipTeam { .. } => {
-                    let qm = CreateQuickModal::new("Transfer Bot Ownership to Team")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Bot ID",
-                            "bot_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "New Team ID",
-                            "team_id",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (bot_id, reason, team_id) = (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::BotTransferOwnershipTeam {
-                                bot_id: bot_id.to_string(),
-                                reason: reason.to_string(),
-                                new_team: team_id.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
-                    }
-                }
-                super::core::RPCMethod::TeamNameEdit { .. } => {
-                    let qm = CreateQuickModal::new("Team Name Edit")
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "Team ID",
-                            "team_id",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Short,
-                            "New Team Name",
-                            "new_name",
-                        ))
-                        .field(CreateInputText::new(
-                            InputTextStyle::Paragraph,
-                            "Reason",
-                            "reason",
-                        ));
-
-                    if let Some(resp) = m.quick_modal(discord, qm).await? {
-                        let inputs = resp.inputs;
-                        let (team_id, new_name, reason) = (&inputs[0], &inputs[1], &inputs[2]);
-
-                        GetResp {
-                            method: super::core::RPCMethod::TeamNameEdit {
-                                team_id: team_id.to_string(),
-                                new_name: new_name.to_string(),
-                                reason: reason.to_string(),
-                            },
-                            interaction: resp.interaction,
-                        }
-                    } else {
-                        return Err("No response".into());
+                            .await?;
+
+                        return Ok(());
                     }
                 }
+            } else {
+                return Err("No response".into());
             }
         } else {
             msg.edit(ctx.discord(), builder.to_prefix_edit().components(vec![]))
@@ -722,6 +202,8 @@ pub async fn rpc(
             cache_http: data.cache_http.clone(),
             pool: data.pool.clone(),
             user_id: ctx.author().id.to_string(),
+            dry_run: dry_run.unwrap_or(false),
+            approved_by: None,
         })
         .await
     {
@@ -757,6 +239,37 @@ pub async fn rpc(
                     .await?;
                 Ok(())
             }
+            super::core::RPCSuccess::DryRun(diff) => {
+                rpc_method
+                    .interaction
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::default().content(format!(
+                                "**Dry run of `{}`, nothing was changed**\n{}",
+                                rpc_method.method,
+                                diff_summary(&diff)
+                            )),
+                        ),
+                    )
+                    .await?;
+                Ok(())
+            }
+            super::core::RPCSuccess::PendingApproval(action_id) => {
+                rpc_method
+                    .interaction
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::default().content(format!(
+                                "`{}` needs a second approval and has been sent to the staff server (action `{}`)",
+                                rpc_method.method, action_id
+                            )),
+                        ),
+                    )
+                    .await?;
+                Ok(())
+            }
         },
         Err(e) => {
             rpc_method
@@ -775,3 +288,632 @@ pub async fn rpc(
         }
     }
 }
+
+/// Reverts an earlier RPC action using the ID of its log entry
+#[poise::command(prefix_command, slash_command, check = "crate::checks::is_staff")]
+pub async fn rpcundo(
+    ctx: Context<'_>,
+    #[description = "The ID of the RPC log entry to revert"] log_id: String,
+    #[description = "Why this action is being reverted"] reason: String,
+) -> Result<(), Error> {
+    let data = ctx.data();
+
+    let method = super::core::RPCMethod::Revert { log_id, reason };
+
+    match method
+        .handle(crate::rpc::core::RPCHandle {
+            cache_http: data.cache_http.clone(),
+            pool: data.pool.clone(),
+            user_id: ctx.author().id.to_string(),
+            dry_run: false,
+            approved_by: None,
+        })
+        .await
+    {
+        Ok(super::core::RPCSuccess::PendingApproval(action_id)) => {
+            ctx.say(format!(
+                "`{}` needs a second approval and has been sent to the staff server (action `{}`)",
+                method, action_id
+            ))
+            .await?;
+        }
+        Ok(_) => {
+            ctx.say("Successfully reverted the action").await?;
+        }
+        Err(e) => {
+            ctx.say(format!("Error performing `{}`: **{}**", method, e))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists or cancels RPC actions scheduled to run later
+#[poise::command(
+    prefix_command,
+    slash_command,
+    check = "crate::checks::is_staff",
+    subcommands("rpc_scheduled_list", "rpc_scheduled_cancel"),
+    subcommand_required
+)]
+pub async fn rpcscheduled(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Lists RPC actions that have not run yet
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "list",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_scheduled_list(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+
+    let actions = super::scheduled::pending(&data.pool).await?;
+
+    if actions.is_empty() {
+        ctx.say("There are no scheduled RPC actions").await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Scheduled RPC Actions")
+        .color(0x00ff00);
+
+    // Discord only allows 25 fields per embed
+    for action in actions.iter().take(25) {
+        embed = embed.field(
+            format!("{} ({})", action.method, action.id),
+            format!(
+                "By <@{}>, runs <t:{}:R>",
+                action.user_id,
+                action.execute_at.timestamp()
+            ),
+            false,
+        );
+    }
+
+    if actions.len() > 25 {
+        embed = embed.description(format!("Showing 25 of {} actions", actions.len()));
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Cancels a scheduled RPC action
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "cancel",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_scheduled_cancel(
+    ctx: Context<'_>,
+    #[description = "The ID of the scheduled action"] id: String,
+) -> Result<(), Error> {
+    let data = ctx.data();
+
+    let id = id.parse::<sqlx::types::Uuid>()?;
+
+    let is_owner = crate::config::CONFIG.owners.contains(&ctx.author().id.0);
+
+    if super::scheduled::cancel(&data.pool, id, &ctx.author().id.to_string(), is_owner).await? {
+        ctx.say("Cancelled the scheduled action").await?;
+    } else {
+        ctx.say("This action does not exist, has already run or was not scheduled by you")
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Manage active RPC identities
+#[poise::command(
+    prefix_command,
+    slash_command,
+    check = "crate::checks::is_staff",
+    subcommands(
+        "rpc_sessions_list",
+        "rpc_sessions_revoke",
+        "rpc_sessions_revoke_user",
+        "rpc_sessions_panic"
+    ),
+    subcommand_required
+)]
+pub async fn rpcsessions(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Records a revocation in the staff logs
+async fn log_revoke(ctx: Context<'_>, data: serde_json::Value) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
+        ctx.author().id.to_string(),
+        "rpc_revoke",
+        data
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Lists active RPC identities, Head staff and above see everyone's
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "list",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_sessions_list(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let author = ctx.author().id.to_string();
+
+    let see_all = RPCPerms::Head.check(&data.pool, &author).await.is_ok();
+
+    let sessions = KeychainData::sessions(&data.pool)
+        .await?
+        .into_iter()
+        .filter(|s| see_all || s.data.user_id == author)
+        .collect::<Vec<_>>();
+
+    if sessions.is_empty() {
+        ctx.say("There are no active RPC identities").await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Active RPC Identities")
+        .color(0xff0000);
+
+    // Discord only allows 25 fields per embed
+    for session in sessions.iter().take(25) {
+        embed = embed.field(
+            session.fingerprint.clone(),
+            format!(
+                "**Owner:** <@{}>\n**Methods:** {}\n**Scope:** {}\n**Uses Left:** {}/{}\n**Reason:** {}\n**Expires:** <t:{}:R>",
+                session.data.user_id,
+                session.data.allowed_methods.join(", "),
+                session.data.scope.describe(),
+                session.data.max_uses - session.data.used,
+                session.data.max_uses,
+                session.data.reason,
+                session.data.expires_at.timestamp()
+            )
+            .chars()
+            .take(1024)
+            .collect::<String>(),
+            false,
+        );
+    }
+
+    if sessions.len() > 25 {
+        embed = embed.description(format!("Showing 25 of {} identities", sessions.len()));
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Revokes an RPC identity by its fingerprint
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "revoke",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_sessions_revoke(
+    ctx: Context<'_>,
+    #[description = "The fingerprint shown in /rpcsessions list"] fingerprint: String,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let author = ctx.author().id.to_string();
+
+    let fingerprint = fingerprint.trim().to_lowercase();
+
+    let session = KeychainData::sessions(&data.pool)
+        .await?
+        .into_iter()
+        .find(|s| s.fingerprint == fingerprint);
+
+    let session = match session {
+        Some(session) => session,
+        None => {
+            ctx.say("This RPC identity does not exist or has already expired")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Staff can always revoke their own identities
+    if session.data.user_id != author {
+        RPCPerms::Head.check(&data.pool, &author).await?;
+    }
+
+    if !KeychainData::revoke_fingerprint(&data.pool, &fingerprint).await? {
+        ctx.say("This RPC identity does not exist or has already expired")
+            .await?;
+        return Ok(());
+    }
+
+    log_revoke(
+        ctx,
+        serde_json::json!({
+            "fingerprint": fingerprint,
+            "owner": session.data.user_id,
+        }),
+    )
+    .await?;
+
+    ctx.say(format!("Revoked RPC identity `{}`", fingerprint))
+        .await?;
+
+    Ok(())
+}
+
+/// Revokes every RPC identity of a user
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "revokeuser",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_sessions_revoke_user(
+    ctx: Context<'_>,
+    #[description = "The user whose identities should be revoked"] user: serenity::User,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let author = ctx.author().id.to_string();
+
+    if user.id != ctx.author().id {
+        RPCPerms::Head.check(&data.pool, &author).await?;
+    }
+
+    let revoked = KeychainData::revoke_user(&data.pool, &user.id.to_string()).await?;
+
+    log_revoke(
+        ctx,
+        serde_json::json!({
+            "owner": user.id.to_string(),
+            "revoked": revoked,
+        }),
+    )
+    .await?;
+
+    ctx.say(format!(
+        "Revoked {} RPC identities of <@{}>",
+        revoked, user.id
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes every RPC identity of every user. Owners only
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "panic",
+    check = "crate::checks::is_staff"
+)]
+pub async fn rpc_sessions_panic(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+
+    RPCPerms::Owner
+        .check(&data.pool, &ctx.author().id.to_string())
+        .await?;
+
+    let revoked = KeychainData::revoke_all(&data.pool).await?;
+
+    log_revoke(
+        ctx,
+        serde_json::json!({
+            "panic": true,
+            "revoked": revoked,
+        }),
+    )
+    .await?;
+
+    ctx.say(format!(
+        "Revoked every RPC identity ({} still active). Anyone using RPC will need to run `/rpcidentify` again",
+        revoked
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// The largest file Discord accepts as an attachment in servers without boosts
+const MAX_ATTACHMENT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Parses a ``YYYY-MM-DD`` date
+fn parse_day(field: &str, v: &str) -> Result<NaiveDate, Error> {
+    Ok(NaiveDate::parse_from_str(v, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid `{}` date, use YYYY-MM-DD: {}", field, e))?)
+}
+
+/// Parses a ``YYYY-MM-DD`` date as midnight UTC
+fn parse_date(field: &str, v: &str) -> Result<DateTime<Utc>, Error> {
+    let date = parse_day(field, v)?;
+
+    let midnight = date.and_hms_opt(0, 0, 0).ok_or("Invalid date")?;
+
+    Ok(Utc.from_utc_datetime(&midnight))
+}
+
+fn log_page_embed(page: &LogPage, page_no: usize) -> CreateEmbed {
+    let mut embed = CreateEmbed::default()
+        .title(format!("RPC Logs (page {})", page_no + 1))
+        .color(0x00ff00);
+
+    if page.entries.is_empty() {
+        return embed.description("No log entries match these filters");
+    }
+
+    for entry in &page.entries {
+        let bot_id = entry
+            .data
+            .get(&entry.method)
+            .and_then(|d| d.get("bot_id"))
+            .and_then(|b| b.as_str());
+
+        let mut value = format!(
+            "By <@{}> <t:{}:R>\n**State:** {}",
+            entry.user_id,
+            entry.created_at.timestamp(),
+            // Error states are the error message, which can be long
+            entry.state.chars().take(200).collect::<String>()
+        );
+
+        if let Some(bot_id) = bot_id {
+            value.push_str(&format!("\n**Bot:** <@{}>", bot_id));
+        }
+
+        if let Some(approved_by) = &entry.approved_by {
+            value.push_str(&format!("\n**Approved By:** <@{}>", approved_by));
+        }
+
+        if let Some(reverted_by) = &entry.reverted_by {
+            value.push_str(&format!("\n**Reverted By:** `{}`", reverted_by));
+        }
+
+        embed = embed.field(format!("{} ({})", entry.method, entry.id), value, false);
+    }
+
+    embed
+}
+
+fn log_page_reply(page: &LogPage, page_no: usize) -> CreateReply {
+    CreateReply::default()
+        .embed(log_page_embed(page, page_no))
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("rpclogs:prev")
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page_no == 0),
+            CreateButton::new("rpclogs:next")
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page.next_cursor.is_none()),
+        ])])
+}
+
+/// Searches the RPC audit log. Head staff and above only
+#[poise::command(prefix_command, slash_command, check = "crate::checks::is_staff")]
+pub async fn rpclogs(
+    ctx: Context<'_>,
+    #[description = "Only show calls made by this staff member"] user: Option<serenity::User>,
+    #[description = "Only show calls of this method"]
+    #[autocomplete = "autocomplete"]
+    method: Option<String>,
+    #[description = "Only show calls acting on this bot"] bot_id: Option<String>,
+    #[description = "pending, success, scheduled, cancelled or error"] state: Option<String>,
+    #[description = "Only show calls on or after this date (YYYY-MM-DD)"] after: Option<String>,
+    #[description = "Only show calls before this date (YYYY-MM-DD)"] before: Option<String>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+
+    RPCPerms::Head
+        .check(&data.pool, &ctx.author().id.to_string())
+        .await?;
+
+    let mut filter = LogFilter {
+        user_id: user.map(|u| u.id.to_string()),
+        method,
+        bot_id,
+        state,
+        after: match after {
+            Some(after) => Some(parse_date("after", &after)?),
+            None => None,
+        },
+        before: match before {
+            Some(before) => Some(parse_date("before", &before)?),
+            None => None,
+        },
+        cursor: None,
+        limit: Some(10),
+    };
+
+    let mut page = logs::query(&data.pool, &filter).await?;
+
+    // The cursor of every page seen so far, so we can go back
+    let mut cursors: Vec<Option<String>> = vec![None];
+
+    let mut msg = ctx
+        .send(log_page_reply(&page, 0))
+        .await?
+        .into_message()
+        .await?;
+
+    let mut interaction = msg
+        .await_component_interactions(ctx.discord())
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(180))
+        .stream();
+
+    while let Some(item) = interaction.next().await {
+        item.defer(&ctx.discord()).await?;
+
+        match item.data.custom_id.as_str() {
+            "rpclogs:next" => {
+                if page.next_cursor.is_none() {
+                    continue;
+                }
+
+                cursors.push(page.next_cursor.clone());
+            }
+            "rpclogs:prev" => {
+                if cursors.len() <= 1 {
+                    continue;
+                }
+
+                cursors.pop();
+            }
+            _ => continue,
+        }
+
+        filter.cursor = cursors.last().cloned().flatten();
+        page = logs::query(&data.pool, &filter).await?;
+
+        msg.edit(
+            ctx,
+            log_page_reply(&page, cursors.len() - 1).to_prefix_edit(),
+        )
+        .await?;
+    }
+
+    // Remove the buttons once the paginator times out
+    msg.edit(
+        ctx,
+        log_page_reply(&page, cursors.len() - 1)
+            .components(vec![])
+            .to_prefix_edit(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Audit log tools
+#[poise::command(
+    prefix_command,
+    slash_command,
+    check = "crate::checks::is_staff",
+    subcommands("audit_export"),
+    subcommand_required
+)]
+pub async fn audit(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Exports the RPC and staff logs as a file. Head staff and above only
+#[poise::command(
+    prefix_command,
+    slash_command,
+    rename = "export",
+    check = "crate::checks::is_staff"
+)]
+pub async fn audit_export(
+    ctx: Context<'_>,
+    #[description = "First day to export (YYYY-MM-DD)"] from: String,
+    #[description = "Last day to export, inclusive (YYYY-MM-DD)"] to: String,
+    #[description = "Only export actions by this staff member"] user: Option<serenity::User>,
+    #[description = "csv or ndjson, defaults to csv"] format: Option<String>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+
+    RPCPerms::Head
+        .check(&data.pool, &ctx.author().id.to_string())
+        .await?;
+
+    let format = match format.as_deref() {
+        None | Some("csv") => ExportFormat::Csv,
+        Some("ndjson") => ExportFormat::Ndjson,
+        Some(f) => return Err(format!("Unknown format `{}`, use csv or ndjson", f).into()),
+    };
+
+    let range = ExportRange::new(parse_day("from", &from)?, parse_day("to", &to)?)?;
+
+    ctx.defer().await?;
+
+    let mut rows = logs::export(data.pool.clone(), range, user.map(|u| u.id.to_string()));
+
+    let mut summary = AuditSummary::default();
+    let mut body = format.header().to_vec();
+    let mut size = body.len();
+    let mut line = Vec::new();
+
+    while let Some(row) = rows.recv().await {
+        let row = row?;
+
+        summary.record(&row);
+
+        line.clear();
+        row.write(format, &mut line)?;
+
+        // Past what Discord accepts the rows are only counted, the file is not sent then
+        size += line.len();
+
+        if size <= MAX_ATTACHMENT_BYTES {
+            body.extend_from_slice(&line);
+        }
+    }
+
+    summary.finish();
+
+    let mut embed = CreateEmbed::default()
+        .title("Audit Export")
+        .description(format!(
+            "**From:** {}\n**To:** {} (inclusive)\n**Rows:** {}\n**RPC calls:** {} ({} failed)\n**Staff actions:** {}",
+            from, to, summary.total, summary.rpc_calls, summary.rpc_errors, summary.staff_actions
+        ));
+
+    // Discord caps embeds at 25 fields
+    for totals in summary.by_user.iter().take(10) {
+        let actions = totals
+            .actions
+            .iter()
+            .map(|(action, count)| format!("{}: {}", action, count))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        embed = embed.field(
+            format!("{} actions", totals.total),
+            format!("<@{}>\n{}", totals.user_id, actions)
+                .chars()
+                .take(1024)
+                .collect::<String>(),
+            true,
+        );
+    }
+
+    if size > MAX_ATTACHMENT_BYTES {
+        embed = embed.field(
+            "File too large",
+            format!(
+                "The export is {:.1} MiB, more than Discord allows. Export a shorter range or use `/logs/export` on the RPC server",
+                size as f64 / (1024.0 * 1024.0)
+            ),
+            false,
+        );
+
+        ctx.send(CreateReply::default().embed(embed)).await?;
+
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .embed(embed)
+            .attachment(CreateAttachment::bytes(body, range.filename(format))),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -1,19 +1,23 @@
+use std::collections::HashMap;
 use std::num::NonZeroU64;
 
-use log::error;
-use poise::serenity_prelude::{
-    ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId, RoleId, UserId,
-};
+use poise::serenity_prelude::{CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId, UserId};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serenity::model::Color;
-use sqlx::{types::Uuid, PgPool};
-use strum_macros::{Display, EnumString, EnumVariantNames};
+use sqlx::{types::Uuid, PgPool, Postgres, Transaction};
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
 use ts_rs::TS;
 
+use super::effects::{RPCDiff, RPCEffects};
+use super::error::RPCFailure;
+use super::events::RPCEvent;
+use super::revert::BeforeImage;
+use crate::impls::resubmissions::{self, ResubmitSource};
 use crate::{impls, Error};
 
-#[derive(Serialize, Deserialize, TS, EnumString, EnumVariantNames, Display, Clone)]
+#[derive(Serialize, Deserialize, TS, JsonSchema, EnumString, EnumVariantNames, Display, Clone)]
 #[ts(export, export_to = ".generated/RPCMethod.ts")]
 #[allow(clippy::enum_variant_names)]
 pub enum RPCMethod {
@@ -33,6 +37,24 @@ pub enum RPCMethod {
         bot_id: String,
         reason: String,
     },
+    BotBulkDeny {
+        bot_ids: Vec<String>,
+        reason: String,
+        /// Reasons for single bots, keyed by bot ID, used instead of ``reason`` for those bots
+        #[serde(default)]
+        reasons: Option<HashMap<String, String>>,
+    },
+    BotBulkUnclaim {
+        bot_ids: Vec<String>,
+        reason: String,
+        /// Reasons for single bots, keyed by bot ID, used instead of ``reason`` for those bots
+        #[serde(default)]
+        reasons: Option<HashMap<String, String>>,
+    },
+    BotRequeue {
+        bot_id: String,
+        reason: String,
+    },
     BotVoteReset {
         bot_id: String,
         reason: String,
@@ -94,15 +116,23 @@ pub enum RPCMethod {
         new_name: String,
         reason: String,
     },
+    Revert {
+        log_id: String,
+        reason: String,
+    },
 }
 
 pub struct RPCHandle {
     pub pool: PgPool,
     pub cache_http: impls::cache::CacheHttpImpl,
     pub user_id: String,
+    /// Runs all checks but rolls back all changes, returning what would have changed instead
+    pub dry_run: bool,
+    /// The second user who approved this call, if the method needs a quorum
+    pub approved_by: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, TS)]
+#[derive(Serialize, Deserialize, TS, JsonSchema, EnumIter)]
 #[ts(export, export_to = ".generated/RPCPerms.ts")]
 pub enum RPCPerms {
     Owner,
@@ -111,212 +141,189 @@ pub enum RPCPerms {
     Staff,
 }
 
-impl RPCMethod {
-    pub fn needs_perms(&self) -> RPCPerms {
+impl RPCPerms {
+    /// Checks that a user has at least these permissions
+    pub async fn check(&self, pool: &PgPool, user_id: &str) -> Result<(), Error> {
         match self {
-            RPCMethod::BotClaim { .. } => RPCPerms::Staff,
-            RPCMethod::BotUnclaim { .. } => RPCPerms::Staff,
-            RPCMethod::BotApprove { .. } => RPCPerms::Staff,
-            RPCMethod::BotDeny { .. } => RPCPerms::Staff,
-            RPCMethod::BotVoteReset { .. } => RPCPerms::Owner,
-            RPCMethod::BotVoteResetAll { .. } => RPCPerms::Owner,
-            RPCMethod::BotUnverify { .. } => RPCPerms::Staff,
-            RPCMethod::BotPremiumAdd { .. } => RPCPerms::Head,
-            RPCMethod::BotPremiumRemove { .. } => RPCPerms::Head,
-            RPCMethod::BotVoteBanAdd { .. } => RPCPerms::Head,
-            RPCMethod::BotVoteBanRemove { .. } => RPCPerms::Head,
-            RPCMethod::BotForceRemove { .. } => RPCPerms::Admin,
-            RPCMethod::BotCertifyAdd { .. } => RPCPerms::Owner,
-            RPCMethod::BotCertifyRemove { .. } => RPCPerms::Owner,
-            RPCMethod::BotVoteCountSet { .. } => RPCPerms::Owner,
-            RPCMethod::BotTransferOwnershipUser { .. } => RPCPerms::Admin,
-            RPCMethod::BotTransferOwnershipTeam { .. } => RPCPerms::Head,
-            RPCMethod::TeamNameEdit { .. } => RPCPerms::Head,
-        }
-    }
-
-    pub fn description(&self) -> String {
-        match self {
-            Self::BotClaim { .. } => {
-                "Claim a bot. Be sure to claim bots that you are going to review!"
-            }
-            Self::BotUnclaim { .. } => {
-                "Unclaim a bot. Be sure to use this if you can't review the bot!"
-            }
-            Self::BotApprove { .. } => "Approve a bot. Needs to be claimed first.",
-            Self::BotDeny { .. } => "Deny a bot. Needs to be claimed first.",
-            Self::BotVoteReset { .. } => "Reset the votes of a bot",
-            Self::BotVoteResetAll { .. } => "Reset the votes of all bots",
-            Self::BotUnverify { .. } => "Unverifies a bot on the list",
-            Self::BotPremiumAdd { .. } => "Adds premium to a bot for a given time period",
-            Self::BotPremiumRemove { .. } => "Removes premium from a bot",
-            Self::BotVoteBanAdd { .. } => "Vote-bans the bot in question",
-            Self::BotVoteBanRemove { .. } => "Removes the vote-ban from the bot in question",
-            Self::BotForceRemove { .. } => "Forcefully removes a bot from the list",
-            Self::BotCertifyAdd { .. } => {
-                "Certifies a bot. Recommended to use apps instead however"
-            }
-            Self::BotCertifyRemove { .. } => "Uncertifies a bot",
-            Self::BotVoteCountSet { .. } => "Sets the vote count of a bot",
-            Self::BotTransferOwnershipUser { .. } => {
-                "Transfers the ownership of a bot to a new user"
-            }
-            Self::BotTransferOwnershipTeam { .. } => {
-                "Transfers the ownership of a bot to a new team"
-            }
-            Self::TeamNameEdit { .. } => "Edits the name of a team",
-        }
-        .to_string()
-    }
-
-    pub fn label(&self) -> String {
-        match self {
-            Self::BotClaim { .. } => "Claim Bot",
-            Self::BotUnclaim { .. } => "Unclaim Bot",
-            Self::BotApprove { .. } => "Approve Bot",
-            Self::BotDeny { .. } => "Deny Bot",
-            Self::BotVoteReset { .. } => "Reset Bot Votes",
-            Self::BotVoteResetAll { .. } => "Reset All Bot Votes",
-            Self::BotUnverify { .. } => "Unverify Bot",
-            Self::BotPremiumAdd { .. } => "Add Premium [Bot]",
-            Self::BotPremiumRemove { .. } => "Remove Premium [Bot]",
-            Self::BotVoteBanAdd { .. } => "Vote Ban Bot",
-            Self::BotVoteBanRemove { .. } => "Unvote Ban Bot",
-            Self::BotForceRemove { .. } => "Force Remove Bot",
-            Self::BotCertifyAdd { .. } => "Certify Bot",
-            Self::BotCertifyRemove { .. } => "Uncertify Bot",
-            Self::BotVoteCountSet { .. } => "Set Bot Vote Count",
-            Self::BotTransferOwnershipUser { .. } => "Set Bot Owner [User]",
-            Self::BotTransferOwnershipTeam { .. } => "Set Bot Owner [Team]",
-            Self::TeamNameEdit { .. } => "Edit Team Name",
-        }
-        .to_string()
-    }
-
-    pub async fn handle(&self, state: RPCHandle) -> Result<RPCSuccess, Error> {
-        // First ensure we have the permissions needed
-        match self.needs_perms() {
             RPCPerms::Owner => {
-                let staff_id_snow = state.user_id.parse::<NonZeroU64>()?;
+                let staff_id_snow = user_id.parse::<NonZeroU64>()?;
 
                 if !crate::config::CONFIG.owners.contains(&staff_id_snow) {
-                    return Err("You need to be an owner to use this method".into());
+                    return Err(RPCFailure::permission_denied(
+                        "You need to be an owner to do this",
+                    )
+                    .into());
                 }
             }
             RPCPerms::Head => {
                 let check = sqlx::query!(
                     "SELECT iblhdev, hadmin FROM users WHERE user_id = $1",
-                    &state.user_id
+                    user_id
                 )
-                .fetch_one(&state.pool)
+                .fetch_one(pool)
                 .await?;
 
                 if !check.iblhdev && !check.hadmin {
-                    return Err("You need to be at least a `Head Staff Manager` or a `Head Developer` to use this method".into());
+                    return Err(RPCFailure::permission_denied("You need to be at least a `Head Staff Manager` or a `Head Developer` to do this").into());
                 }
             }
             RPCPerms::Admin => {
-                let check =
-                    sqlx::query!("SELECT admin FROM users WHERE user_id = $1", &state.user_id)
-                        .fetch_one(&state.pool)
-                        .await?;
+                let check = sqlx::query!("SELECT admin FROM users WHERE user_id = $1", user_id)
+                    .fetch_one(pool)
+                    .await?;
 
                 if !check.admin {
-                    return Err(
-                        "You need to be at least a `Staff Manager` to use this method".into(),
-                    );
+                    return Err(RPCFailure::permission_denied(
+                        "You need to be at least a `Staff Manager` to do this",
+                    )
+                    .into());
                 }
             }
             RPCPerms::Staff => {
-                let check =
-                    sqlx::query!("SELECT staff FROM users WHERE user_id = $1", &state.user_id)
-                        .fetch_one(&state.pool)
-                        .await?;
+                let check = sqlx::query!("SELECT staff FROM users WHERE user_id = $1", user_id)
+                    .fetch_one(pool)
+                    .await?;
 
                 if !check.staff {
-                    return Err("You need to be a staff member to use this method".into());
+                    return Err(RPCFailure::permission_denied(
+                        "You need to be a staff member to do this",
+                    )
+                    .into());
                 }
             }
         }
 
-        // Also ensure that onboarding has happened
-        let onboard_state = sqlx::query!(
-            "SELECT staff, staff_onboard_state FROM users WHERE user_id = $1",
-            &state.user_id
-        )
-        .fetch_one(&state.pool)
-        .await?;
+        Ok(())
+    }
+}
+
+impl RPCMethod {
+    pub fn needs_perms(&self) -> RPCPerms {
+        self.spec().perms
+    }
+
+    pub fn description(&self) -> String {
+        self.spec().description.to_string()
+    }
+
+    pub fn label(&self) -> String {
+        self.spec().label.to_string()
+    }
+
+    /// The oldest RPC protocol version that supports this method
+    pub fn min_protocol(&self) -> u8 {
+        self.spec().min_protocol
+    }
+
+    /// Checks that a user has the permissions needed to use this method
+    pub async fn check_perms(&self, pool: &PgPool, user_id: &str) -> Result<(), Error> {
+        self.needs_perms().check(pool, user_id).await
+    }
+
+    /// Whether this method needs a second eligible user to approve it before it runs
+    pub fn needs_quorum(&self) -> bool {
+        crate::config::CONFIG
+            .rpc_quorum_methods
+            .contains(&self.to_string())
+    }
+
+    pub async fn handle(&self, state: RPCHandle) -> Result<RPCSuccess, Error> {
+        precheck(&state, std::slice::from_ref(self)).await?;
+
+        if state.dry_run {
+            return self.dry_run(&state).await;
+        }
+
+        // Methods needing a quorum are saved for a second user to approve instead of running now
+        if self.needs_quorum() && state.approved_by.is_none() {
+            let action_id = super::quorum::request_approval(&state, self).await?;
 
-        if onboard_state.staff_onboard_state != "completed" {
-            return Err("You need to complete onboarding in order to use RPC!".into());
+            return Ok(RPCSuccess::PendingApproval(action_id.to_string()));
         }
 
         // Insert into rpc_logs
-        let id = sqlx::query!(
-            "INSERT INTO rpc_logs (method, user_id, data) VALUES ($1, $2, $3) RETURNING id",
-            self.to_string(),
-            &state.user_id,
-            json!(self)
-        )
-        .fetch_one(&state.pool)
-        .await?;
+        let log_id = self.log(&state, None).await?;
 
-        // Get number of requests in the last 7 minutes
-        let res = sqlx::query!(
-            "SELECT COUNT(*) FROM rpc_logs WHERE user_id = $1 AND NOW() - created_at < INTERVAL '7 minutes'",
-            &state.user_id
-        )
-        .fetch_one(&state.pool)
-        .await
-        .map_err(|_| "Failed to get ratelimit count")?;
+        self.run(&state, log_id).await
+    }
 
-        let count = res.count.unwrap_or_default();
+    /// Runs the method and rolls all of its changes back, returning what would have changed
+    ///
+    /// Dry runs are not logged in ``rpc_logs`` as nothing is changed by them
+    pub(super) async fn dry_run(&self, state: &RPCHandle) -> Result<RPCSuccess, Error> {
+        let mut tx = state.pool.begin().await?;
+        let mut effects = RPCEffects::default();
 
-        if count > 5 {
-            sqlx::query!(
-                "UPDATE users SET api_token = $2 WHERE user_id = $1",
-                &state.user_id,
-                impls::crypto::gen_random(136)
-            )
-            .execute(&state.pool)
-            .await
-            .map_err(|_| "Failed to reset user token")?;
-
-            return Err(
-                "Rate limit exceeded. Wait 5-10 minutes and try again?"
-                    .into(),
-            );
+        let res = self.handle_method(state, &mut tx, &mut effects).await;
+
+        tx.rollback().await?;
+
+        res.map(|_| RPCSuccess::DryRun(effects.diff()))
+    }
+
+    /// Runs an already logged method, all database changes are made in a single transaction
+    pub(super) async fn run(&self, state: &RPCHandle, log_id: Uuid) -> Result<RPCSuccess, Error> {
+        let mut tx = state.pool.begin().await?;
+        let mut effects = RPCEffects::default();
+
+        let resp = match self.handle_method(state, &mut tx, &mut effects).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                tx.rollback().await?;
+
+                set_log_state(&state.pool, log_id, &e.to_string()).await?;
+
+                return Err(e);
+            }
+        };
+
+        if let Err(e) = record_success(&mut tx, log_id, &mut effects).await {
+            tx.rollback().await?;
+
+            set_log_state(&state.pool, log_id, &e.to_string()).await?;
+
+            return Err(e);
         }
 
-        // Now we can handle the method
-        let resp = self.handle_method(&state).await;
+        if let Err(e) = tx.commit().await {
+            set_log_state(&state.pool, log_id, &e.to_string()).await?;
 
-        if resp.is_ok() {
-            sqlx::query!(
-                "UPDATE rpc_logs SET state = $1 WHERE id = $2",
-                "success",
-                id.id
-            )
-            .execute(&state.pool)
-            .await?;
-        } else {
-            sqlx::query!(
-                "UPDATE rpc_logs SET state = $1 WHERE id = $2",
-                resp.as_ref()
-                    .err()
-                    .ok_or("Err variant doesnt have an error!")?
-                    .to_string(),
-                id.id
-            )
-            .execute(&state.pool)
-            .await?;
+            return Err(e.into());
         }
 
-        resp
+        // Discord side effects are only dispatched once committed, a failure here does not undo the method
+        effects.dispatch(&state.cache_http).await;
+
+        Ok(resp)
+    }
+
+    /// Inserts the call into ``rpc_logs``, returning the ID of the log entry
+    pub(super) async fn log(
+        &self,
+        state: &RPCHandle,
+        batch_id: Option<Uuid>,
+    ) -> Result<Uuid, Error> {
+        let rec = sqlx::query!(
+            "INSERT INTO rpc_logs (method, user_id, data, approved_by, batch_id) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+            self.to_string(),
+            &state.user_id,
+            json!(self),
+            state.approved_by,
+            batch_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+
+        Ok(rec.id)
     }
 
     /// The low-level method handler
-    async fn handle_method(&self, state: &RPCHandle) -> Result<RPCSuccess, Error> {
+    pub(super) async fn handle_method(
+        &self,
+        state: &RPCHandle,
+        tx: &mut Transaction<'_, Postgres>,
+        effects: &mut RPCEffects,
+    ) -> Result<RPCSuccess, Error> {
         match self {
             RPCMethod::BotClaim { bot_id, force } => {
                 // Check if its claimed by someone
@@ -324,37 +331,41 @@ impl RPCMethod {
                     "SELECT type, claimed_by FROM bots WHERE bot_id = $1",
                     bot_id
                 )
-                .fetch_one(&state.pool)
+                .fetch_one(&mut *tx)
                 .await?;
 
                 if claimed.r#type != "pending" {
-                    return Err("This bot is not pending review".into());
+                    return Err(RPCFailure::invalid_state("This bot is not pending review").into());
                 }
 
                 if claimed.r#type == "testbot" {
-                    return Err("This bot is a test bot".into());
+                    return Err(RPCFailure::invalid_state("This bot is a test bot").into());
                 }
 
                 let bot_owner = crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?;
 
                 if !force {
                     if let Some(claimed_by) = claimed.claimed_by {
-                        return Err(
-                            format!("This bot is already claimed by <@{}>", claimed_by).into()
-                        );
+                        return Err(RPCFailure::invalid_state(format!(
+                            "This bot is already claimed by <@{}>",
+                            claimed_by
+                        ))
+                        .into());
                     }
                 }
 
                 // Claim it
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET last_claimed = NOW(), claimed_by = $1 WHERE bot_id = $2",
                     &state.user_id,
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
-                sqlx::query!(
+                effects.touched("bots", res);
+
+                let res = sqlx::query!(
                     "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
                     &state.user_id,
                     "claimed",
@@ -363,9 +374,11 @@ impl RPCMethod {
                         "claimed_by_prev": claimed.claimed_by,
                     })
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("staff_general_logs", res);
+
                 // Send a message to the bot owner
                 let msg = CreateMessage::default()
                     .content(format!("<@{}>", bot_owner))
@@ -380,9 +393,13 @@ impl RPCMethod {
                             )),
                     );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::BotClaimed {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    force: *force,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -392,31 +409,35 @@ impl RPCMethod {
                     "SELECT type, claimed_by, owner FROM bots WHERE bot_id = $1",
                     bot_id
                 )
-                .fetch_one(&state.pool)
+                .fetch_one(&mut *tx)
                 .await?;
 
                 if claimed.r#type == "testbot" {
-                    return Err("This bot is a test bot".into());
+                    return Err(RPCFailure::invalid_state("This bot is a test bot").into());
                 }
 
                 if claimed.r#type != "pending" {
-                    return Err("This bot is not pending review".into());
+                    return Err(RPCFailure::invalid_state("This bot is not pending review").into());
                 }
 
                 let bot_owner = crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?;
 
                 if claimed.claimed_by.is_none() {
-                    return Err(format!("<@{}> is not claimed", bot_id).into());
+                    return Err(
+                        RPCFailure::invalid_state(format!("<@{}> is not claimed", bot_id)).into(),
+                    );
                 }
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET claimed_by = NULL, type = 'pending' WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
-                sqlx::query!(
+                effects.touched("bots", res);
+
+                let res = sqlx::query!(
                     "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
                     &state.user_id,
                     "unclaimed",
@@ -425,9 +446,11 @@ impl RPCMethod {
                         "claimed_by_prev": claimed.claimed_by,
                     })
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("staff_general_logs", res);
+
                 let msg = CreateMessage::new()
                     .content(format!("<@{}>", bot_owner))
                     .embed(
@@ -443,9 +466,13 @@ impl RPCMethod {
                             )),
                     );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::BotUnclaimed {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    reason: reason.to_string(),
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -454,21 +481,21 @@ impl RPCMethod {
                     "SELECT type, claimed_by, last_claimed FROM bots WHERE bot_id = $1",
                     bot_id
                 )
-                .fetch_one(&state.pool)
+                .fetch_one(&mut *tx)
                 .await?;
 
                 if claimed.r#type != "pending" {
-                    return Err("Bot is not pending review?".into());
+                    return Err(RPCFailure::invalid_state("Bot is not pending review?").into());
                 }
 
                 if claimed.claimed_by.is_none()
                     || claimed.claimed_by.as_ref().unwrap().is_empty()
                     || claimed.last_claimed.is_none()
                 {
-                    return Err(format!(
+                    return Err(RPCFailure::invalid_state(format!(
                         "<@{}> is not claimed? Do ``/claim`` to claim this bot first!",
                         bot_id
-                    )
+                    ))
                     .into());
                 }
 
@@ -476,7 +503,7 @@ impl RPCMethod {
                 let last_claimed = claimed.last_claimed.unwrap();
 
                 if (start_time - last_claimed).num_minutes() < 5 {
-                    return Err("Whoa there! You need to test this bot for at least 5 minutes (recommended: 10-20 minutes) before being able to approve/deny it!".into());
+                    return Err(RPCFailure::invalid_state("Whoa there! You need to test this bot for at least 5 minutes (recommended: 10-20 minutes) before being able to approve/deny it!").into());
                 }
 
                 // Find bot in testing server
@@ -490,19 +517,21 @@ impl RPCMethod {
                     let member = guild.members.contains_key(&UserId(bot_id.parse()?));
 
                     if !member {
-                        return Err("Bot is not in testing server. Please ensure this bot is in the testing server when approving. It will then be kicked by Arcadia when added to main server".into());
+                        return Err(RPCFailure::invalid_state("Bot is not in testing server. Please ensure this bot is in the testing server when approving. It will then be kicked by Arcadia when added to main server").into());
                     }
                 }
 
                 let ping = crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?;
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET type = 'approved', claimed_by = NULL WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::default()
                     .content(format!("<@!{}>", ping))
                     .embed(
@@ -524,9 +553,13 @@ impl RPCMethod {
                             .color(0x00ff00),
                     );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::BotApproved {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    reason: reason.to_string(),
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 let bot_owners = crate::impls::utils::get_bot_members(bot_id, &state.pool).await?;
 
@@ -542,24 +575,16 @@ impl RPCMethod {
                         .is_some()
                     {
                         // Add role to user
-                        if let Err(e) = state
-                            .cache_http
-                            .http
-                            .add_member_role(
-                                GuildId(crate::config::CONFIG.servers.main),
-                                owner_snow,
-                                RoleId(crate::config::CONFIG.roles.bot_developer),
-                                Some("Autorole due to bots owned"),
-                            )
-                            .await
-                        {
-                            error!("Failed to add role to user: {}", e);
-                        }
+                        effects.add_role(
+                            owner_snow,
+                            crate::config::CONFIG.roles.bot_developer,
+                            "Autorole due to bots owned",
+                        );
                     }
                 }
 
                 let invite_data = sqlx::query!("SELECT invite FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 Ok(RPCSuccess::Content(invite_data.invite))
@@ -569,21 +594,21 @@ impl RPCMethod {
                     "SELECT type, claimed_by, owner, last_claimed FROM bots WHERE bot_id = $1",
                     bot_id
                 )
-                .fetch_one(&state.pool)
+                .fetch_one(&mut *tx)
                 .await?;
 
                 if claimed.r#type != "pending" {
-                    return Err("Bot is not pending review?".into());
+                    return Err(RPCFailure::invalid_state("Bot is not pending review?").into());
                 }
 
                 if claimed.claimed_by.is_none()
                     || claimed.claimed_by.as_ref().unwrap().is_empty()
                     || claimed.last_claimed.is_none()
                 {
-                    return Err(format!(
+                    return Err(RPCFailure::invalid_state(format!(
                         "<@{}> is not claimed? Do ``/claim`` to claim this bot first!",
                         bot_id
-                    )
+                    ))
                     .into());
                 }
 
@@ -591,18 +616,20 @@ impl RPCMethod {
                 let last_claimed = claimed.last_claimed.unwrap();
 
                 if (start_time - last_claimed).num_minutes() < 5 {
-                    return Err("Whoa there! You need to test this bot for at least 5 minutes (recommended: 10-20 minutes) before being able to approve/deny it!".into());
+                    return Err(RPCFailure::invalid_state("Whoa there! You need to test this bot for at least 5 minutes (recommended: 10-20 minutes) before being able to approve/deny it!").into());
                 }
 
                 let ping = crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?;
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET type = 'denied', claimed_by = NULL WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().content(format!("<@!{}>", ping)).embed(
                     CreateEmbed::default()
                         .title("Bot Denied!")
@@ -621,30 +648,215 @@ impl RPCMethod {
                         .color(0x00ff00),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
+                effects.event(RPCEvent::BotDenied {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    reason: reason.to_string(),
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
+
+                Ok(RPCSuccess::NoContent)
+            }
+            RPCMethod::BotBulkDeny {
+                bot_ids,
+                reason,
+                reasons,
+            } => {
+                let bots = bulk_pending_bots(state, tx, bot_ids, reasons).await?;
+
+                effects.before_image(
+                    BeforeImage::bot_claims(
+                        tx,
+                        &bots.iter().map(|b| b.bot_id.clone()).collect::<Vec<_>>(),
+                    )
+                    .await?,
+                );
+
+                for bot in &bots {
+                    let reason = bot.reason(reason, reasons);
+
+                    let res = sqlx::query!(
+                        "UPDATE bots SET type = 'denied', claimed_by = NULL WHERE bot_id = $1",
+                        &bot.bot_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    effects.touched("bots", res);
+
+                    let res = sqlx::query!(
+                        "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
+                        &state.user_id,
+                        "bulk_denied",
+                        json!({
+                            "bot_id": bot.bot_id,
+                            "reason": reason,
+                            "claimed_by_prev": bot.claimed_by,
+                            "note": BULK_AUDIT_NOTE,
+                        })
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    effects.touched("staff_general_logs", res);
+
+                    effects.event(RPCEvent::BotDenied {
+                        bot_id: bot.bot_id.clone(),
+                        by: state.user_id.clone(),
+                        reason,
+                    });
+                }
+
+                effects.message(
+                    crate::config::CONFIG.channels.mod_logs,
+                    bulk_summary(state, "Bots Denied!", &bots, reason, reasons),
+                );
+
+                Ok(RPCSuccess::Content(format!("Denied {} bots", bots.len())))
+            }
+            RPCMethod::BotBulkUnclaim {
+                bot_ids,
+                reason,
+                reasons,
+            } => {
+                let bots = bulk_pending_bots(state, tx, bot_ids, reasons).await?;
+
+                effects.before_image(
+                    BeforeImage::bot_claims(
+                        tx,
+                        &bots.iter().map(|b| b.bot_id.clone()).collect::<Vec<_>>(),
+                    )
+                    .await?,
+                );
+
+                for bot in &bots {
+                    let reason = bot.reason(reason, reasons);
+
+                    let res = sqlx::query!(
+                        "UPDATE bots SET claimed_by = NULL WHERE bot_id = $1",
+                        &bot.bot_id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    effects.touched("bots", res);
+
+                    let res = sqlx::query!(
+                        "INSERT INTO staff_general_logs (user_id, action, data) VALUES ($1, $2, $3)",
+                        &state.user_id,
+                        "bulk_unclaimed",
+                        json!({
+                            "bot_id": bot.bot_id,
+                            "reason": reason,
+                            "claimed_by_prev": bot.claimed_by,
+                            "note": BULK_AUDIT_NOTE,
+                        })
+                    )
+                    .execute(&mut *tx)
                     .await?;
 
+                    effects.touched("staff_general_logs", res);
+
+                    effects.event(RPCEvent::BotUnclaimed {
+                        bot_id: bot.bot_id.clone(),
+                        by: state.user_id.clone(),
+                        reason,
+                    });
+                }
+
+                effects.message(
+                    crate::config::CONFIG.channels.mod_logs,
+                    bulk_summary(state, "Bots Unclaimed!", &bots, reason, reasons),
+                );
+
+                Ok(RPCSuccess::Content(format!(
+                    "Unclaimed {} bots",
+                    bots.len()
+                )))
+            }
+            RPCMethod::BotRequeue { bot_id, reason } => {
+                let requeued = resubmissions::requeue(
+                    tx,
+                    bot_id,
+                    &state.user_id,
+                    ResubmitSource::Staff,
+                    reason,
+                )
+                .await?;
+
+                // Only denied bots can be requeued, so that is the type a revert goes back to
+                effects.before_image(BeforeImage::BotRequeue {
+                    bot_id: bot_id.to_string(),
+                    r#type: "denied".to_string(),
+                    resubmission_id: requeued.resubmission_id.to_string(),
+                });
+
+                effects.touched("bots", requeued.bots);
+                effects.touched("bot_resubmissions", requeued.resubmissions);
+
+                let ping = crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?;
+
+                let msg = CreateMessage::new().content(format!("<@!{}>", ping)).embed(
+                    CreateEmbed::default()
+                        .title("Bot Requeued!")
+                        .url(format!(
+                            "{}/bots/{}",
+                            crate::config::CONFIG.frontend_url,
+                            bot_id
+                        ))
+                        .description(format!(
+                            "<@{}> has moved <@{}> back into the queue",
+                            &state.user_id, bot_id
+                        ))
+                        .field("Reason", reason, false)
+                        .field(
+                            "Previous Denial",
+                            requeued
+                                .denial
+                                .map(|d| d.describe())
+                                .unwrap_or_else(|| "*Unknown*".to_string())
+                                .chars()
+                                .take(1024)
+                                .collect::<String>(),
+                            false,
+                        )
+                        .color(Color::BLURPLE),
+                );
+
+                effects.event(RPCEvent::BotResubmitted {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    reason: reason.to_string(),
+                    by_owner: false,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
+
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotVoteReset { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!("UPDATE bots SET votes = 0 WHERE bot_id = $1", bot_id)
-                    .execute(&state.pool)
+                let res = sqlx::query!("UPDATE bots SET votes = 0 WHERE bot_id = $1", bot_id)
+                    .execute(&mut *tx)
                     .await?;
 
-                sqlx::query!("DELETE FROM votes WHERE bot_id = $1", bot_id)
-                    .execute(&state.pool)
+                effects.touched("bots", res);
+
+                let res = sqlx::query!("DELETE FROM votes WHERE bot_id = $1", bot_id)
+                    .execute(&mut *tx)
                     .await?;
 
+                effects.touched("votes", res);
+
                 let msg = CreateMessage::default().embed(
                     CreateEmbed::default()
                         .title("__Bot Vote Reset!__")
@@ -655,20 +867,20 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotVoteResetAll { reason } => {
-                sqlx::query!("UPDATE bots SET votes = 0")
-                    .execute(&state.pool)
+                let res = sqlx::query!("UPDATE bots SET votes = 0")
+                    .execute(&mut *tx)
                     .await?;
 
-                sqlx::query!("DELETE FROM votes")
-                    .execute(&state.pool)
-                    .await?;
+                effects.touched("bots", res);
+
+                let res = sqlx::query!("DELETE FROM votes").execute(&mut *tx).await?;
+
+                effects.touched("votes", res);
 
                 let msg = CreateMessage::default().embed(
                     CreateEmbed::default()
@@ -679,40 +891,39 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotUnverify { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                let bot_type_rec = sqlx::query!(
-                    "SELECT type FROM bots WHERE bot_id = $1",
-                    bot_id
-                )
-                .fetch_one(&state.pool)
-                .await?;
+                let bot_type_rec = sqlx::query!("SELECT type FROM bots WHERE bot_id = $1", bot_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
 
                 if bot_type_rec.r#type == "certified" {
-                    return Err("Certified bots cannot be unverified".into());
+                    return Err(
+                        RPCFailure::invalid_state("Certified bots cannot be unverified").into(),
+                    );
                 }
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET type = 'pending', claimed_by = NULL WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::default().embed(
                     CreateEmbed::default()
                         .title("__Bot Unverified For Futher Review!__")
@@ -723,9 +934,13 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::BotUnverified {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    reason: reason.to_string(),
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotPremiumAdd {
@@ -735,22 +950,26 @@ impl RPCMethod {
             } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
+                effects.before_image(BeforeImage::bot_premium(tx, bot_id).await?);
+
                 // Set premium_period_length which is a postgres interval
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET start_premium_period = NOW(), premium_period_length = make_interval(hours => $1), premium = true WHERE bot_id = $2",
                     time_period_hours,
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Premium Added!")
@@ -765,27 +984,33 @@ impl RPCMethod {
                         .color(0x00ff00),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::PremiumChanged {
+                    bot_id: bot_id.to_string(),
+                    by: Some(state.user_id.clone()),
+                    premium: true,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotPremiumRemove { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
                 // Set premium_period_length which is a postgres interval
-                sqlx::query!("UPDATE bots SET premium = false WHERE bot_id = $1", bot_id)
-                    .execute(&state.pool)
+                let res = sqlx::query!("UPDATE bots SET premium = false WHERE bot_id = $1", bot_id)
+                    .execute(&mut *tx)
                     .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Premium Removed!")
@@ -800,29 +1025,37 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::PremiumChanged {
+                    bot_id: bot_id.to_string(),
+                    by: Some(state.user_id.clone()),
+                    premium: false,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotVoteBanAdd { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!(
+                effects.before_image(BeforeImage::bot_vote_ban(tx, bot_id).await?);
+
+                let res = sqlx::query!(
                     "UPDATE bots SET vote_banned = true WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Vote Ban Edit!")
@@ -837,29 +1070,35 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::VoteBanChanged {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    vote_banned: true,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotVoteBanRemove { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET vote_banned = false WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Vote Ban Removed!")
@@ -874,9 +1113,13 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.event(RPCEvent::VoteBanChanged {
+                    bot_id: bot_id.to_string(),
+                    by: state.user_id.clone(),
+                    vote_banned: false,
+                });
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -887,23 +1130,28 @@ impl RPCMethod {
             } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
                 let bot_id_snow = bot_id.parse::<NonZeroU64>()?;
 
                 if crate::config::CONFIG.protected_bots.contains(&bot_id_snow) && *kick {
-                    return Err("You can't force delete this bot with 'kick' enabled!".into());
+                    return Err(RPCFailure::invalid_argument(
+                        "You can't force delete this bot with 'kick' enabled!",
+                    )
+                    .into());
                 }
 
-                sqlx::query!("DELETE FROM bots WHERE bot_id = $1", bot_id)
-                    .execute(&state.pool)
+                let res = sqlx::query!("DELETE FROM bots WHERE bot_id = $1", bot_id)
+                    .execute(&mut *tx)
                     .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Force Deleted!")
@@ -918,9 +1166,7 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 if *kick {
                     // Check that the bot is in the server
@@ -931,14 +1177,10 @@ impl RPCMethod {
                     );
 
                     if bot.is_some() {
-                        GuildId(crate::config::CONFIG.servers.main)
-                            .member(&state.cache_http, UserId(bot_id.parse()?))
-                            .await?
-                            .kick_with_reason(
-                                &state.cache_http,
-                                &(state.user_id.to_string() + ":" + reason),
-                            )
-                            .await?;
+                        effects.kick(
+                            UserId(bot_id_snow),
+                            state.user_id.to_string() + ":" + reason,
+                        );
                     }
                 }
 
@@ -947,20 +1189,24 @@ impl RPCMethod {
             RPCMethod::BotCertifyAdd { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!(
+                effects.before_image(BeforeImage::bot_type(tx, bot_id).await?);
+
+                let res = sqlx::query!(
                     "UPDATE bots SET type = 'certified' WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Force Certified!")
@@ -973,29 +1219,29 @@ impl RPCMethod {
                         .color(0xff0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
             RPCMethod::BotCertifyRemove { bot_id, reason } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET type = 'approved' WHERE bot_id = $1",
                     bot_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Uncertified!")
@@ -1010,9 +1256,7 @@ impl RPCMethod {
                         .color(0xff0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -1023,21 +1267,23 @@ impl RPCMethod {
             } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
-                sqlx::query!(
+                let res = sqlx::query!(
                     "UPDATE bots SET votes = $2 WHERE bot_id = $1",
                     bot_id,
                     count
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Vote Count Updated!")
@@ -1053,9 +1299,7 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -1066,31 +1310,38 @@ impl RPCMethod {
             } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
                 // Check that the bot is not in a team
                 let team_owner =
                     sqlx::query!("SELECT team_owner FROM bots WHERE bot_id = $1", bot_id)
-                        .fetch_one(&state.pool)
+                        .fetch_one(&mut *tx)
                         .await?;
 
                 if team_owner.team_owner.is_some() {
-                    return Err("Bot is in a team. Please use BotTransferOwnershipTeam".into());
+                    return Err(RPCFailure::invalid_state(
+                        "Bot is in a team. Please use BotTransferOwnershipTeam",
+                    )
+                    .into());
                 }
 
-                sqlx::query!(
+                effects.before_image(BeforeImage::bot_owner(tx, bot_id).await?);
+
+                let res = sqlx::query!(
                     "UPDATE bots SET owner = $2 WHERE bot_id = $1",
                     bot_id,
                     new_owner
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Ownership Force Update!")
@@ -1105,9 +1356,7 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -1118,37 +1367,44 @@ impl RPCMethod {
             } => {
                 // Ensure the bot actually exists
                 let bot = sqlx::query!("SELECT COUNT(*) FROM bots WHERE bot_id = $1", bot_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if bot.count.unwrap_or_default() == 0 {
-                    return Err("Bot does not exist".into());
+                    return Err(RPCFailure::not_found("Bot does not exist").into());
                 }
 
                 // Parse the team ID
                 let team_id = match new_team.parse::<Uuid>() {
                     Ok(id) => id,
-                    Err(_) => return Err("Invalid team ID".into()),
+                    Err(_) => return Err(RPCFailure::invalid_argument("Invalid team ID").into()),
                 };
 
                 // Check that the bot is not in a team
                 let team_owner =
                     sqlx::query!("SELECT team_owner FROM bots WHERE bot_id = $1", bot_id)
-                        .fetch_one(&state.pool)
+                        .fetch_one(&mut *tx)
                         .await?;
 
                 if team_owner.team_owner.is_none() {
-                    return Err("Bot is not in a team. Please use BotTransferOwnership".into());
+                    return Err(RPCFailure::invalid_state(
+                        "Bot is not in a team. Please use BotTransferOwnership",
+                    )
+                    .into());
                 }
 
-                sqlx::query!(
+                effects.before_image(BeforeImage::bot_owner(tx, bot_id).await?);
+
+                let res = sqlx::query!(
                     "UPDATE bots SET team_owner = $2 WHERE bot_id = $1",
                     bot_id,
                     team_id
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("bots", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Ownership Force Update!")
@@ -1163,9 +1419,7 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -1174,37 +1428,33 @@ impl RPCMethod {
                 new_name,
                 reason,
             } => {
-                if new_name.len() > 32 {
-                    return Err("Team name is too long".into());
-                }
-
-                if new_name.len() < 3 {
-                    return Err("Team name is too short".into());
-                }
-
                 // Parse the team ID
                 let team_id = match team_id.parse::<Uuid>() {
                     Ok(id) => id,
-                    Err(_) => return Err("Invalid team ID".into()),
+                    Err(_) => return Err(RPCFailure::invalid_argument("Invalid team ID").into()),
                 };
 
                 // Ensure the team actually exists
                 let team = sqlx::query!("SELECT COUNT(*) FROM teams WHERE id = $1", team_id)
-                    .fetch_one(&state.pool)
+                    .fetch_one(&mut *tx)
                     .await?;
 
                 if team.count.unwrap_or_default() == 0 {
-                    return Err("Team does not exist".into());
+                    return Err(RPCFailure::not_found("Team does not exist").into());
                 }
 
-                sqlx::query!(
+                effects.before_image(BeforeImage::team_name(tx, team_id).await?);
+
+                let res = sqlx::query!(
                     "UPDATE teams SET name = $2 WHERE id = $1",
                     team_id,
                     new_name
                 )
-                .execute(&state.pool)
+                .execute(&mut *tx)
                 .await?;
 
+                effects.touched("teams", res);
+
                 let msg = CreateMessage::new().embed(
                     CreateEmbed::default()
                         .title("Bot Ownership Force Update!")
@@ -1221,9 +1471,91 @@ impl RPCMethod {
                         .color(0xFF0000),
                 );
 
-                ChannelId(crate::config::CONFIG.channels.mod_logs)
-                    .send_message(&state.cache_http, msg)
-                    .await?;
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
+
+                Ok(RPCSuccess::NoContent)
+            }
+            RPCMethod::Revert { log_id, reason } => {
+                let log_id = match log_id.parse::<Uuid>() {
+                    Ok(id) => id,
+                    Err(_) => return Err(RPCFailure::invalid_argument("Invalid log ID").into()),
+                };
+
+                // Lock the log entry so it can only be reverted once
+                let log = sqlx::query!(
+                    "SELECT data, state, before_image, after_image, reverted_by FROM rpc_logs WHERE id = $1 FOR UPDATE",
+                    log_id
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                let log = match log {
+                    Some(log) => log,
+                    None => return Err(RPCFailure::not_found("Log entry does not exist").into()),
+                };
+
+                if log.state != "success" {
+                    return Err(RPCFailure::invalid_state(
+                        "Only successful actions can be reverted",
+                    )
+                    .into());
+                }
+
+                if log.reverted_by.is_some() {
+                    return Err(
+                        RPCFailure::invalid_state("This action has already been reverted").into(),
+                    );
+                }
+
+                let before_image = match log.before_image {
+                    Some(image) => serde_json::from_value::<BeforeImage>(image)?,
+                    None => {
+                        return Err(
+                            RPCFailure::invalid_state("This action cannot be reverted").into()
+                        )
+                    }
+                };
+
+                // Reverting needs the same permissions as the original method
+                let original = serde_json::from_value::<RPCMethod>(log.data)?;
+
+                original.check_perms(&state.pool, &state.user_id).await?;
+
+                // Never overwrite changes made after the action, those need to be reverted by hand
+                let after_image = match log.after_image {
+                    Some(image) => serde_json::from_value::<BeforeImage>(image)?,
+                    None => {
+                        return Err(
+                            RPCFailure::invalid_state("This action cannot be reverted").into()
+                        )
+                    }
+                };
+
+                if before_image.current(tx).await? != after_image {
+                    return Err(RPCFailure::invalid_state(
+                        "This has been changed again since the action, revert it by hand instead",
+                    )
+                    .into());
+                }
+
+                before_image.restore(tx, effects).await?;
+
+                effects.reverts(log_id);
+
+                let msg = CreateMessage::new().embed(
+                    CreateEmbed::default()
+                        .title("Action Reverted!")
+                        .description(format!("<@{}> has reverted `{}`", state.user_id, original))
+                        .field("Log ID", log_id.to_string(), true)
+                        .field("Change", before_image.describe(), true)
+                        .field("Reason", reason, true)
+                        .footer(CreateEmbedFooter::new(
+                            "Contact support if you think this is a mistake",
+                        ))
+                        .color(0xFFA500),
+                );
+
+                effects.message(crate::config::CONFIG.channels.mod_logs, msg);
 
                 Ok(RPCSuccess::NoContent)
             }
@@ -1231,9 +1563,243 @@ impl RPCMethod {
     }
 }
 
+/// Checks that a call may be made now, before it is run, scheduled or saved for approval
+///
+/// All of ``methods`` are rate limited together, so a batch is either charged in full or rejected
+pub(super) async fn precheck(state: &RPCHandle, methods: &[RPCMethod]) -> Result<(), Error> {
+    check_access(state, methods).await?;
+
+    // Approved calls were already rate limited when the approval was requested
+    if state.approved_by.is_none() {
+        super::ratelimit::check(state, methods).await?;
+    }
+
+    Ok(())
+}
+
+/// The checks of ``precheck`` that do not take a rate limit token
+pub(super) async fn check_access(state: &RPCHandle, methods: &[RPCMethod]) -> Result<(), Error> {
+    for method in methods {
+        // Reject bad input before anything touches the database
+        method.validate()?;
+
+        // Then ensure we have the permissions needed
+        method.check_perms(&state.pool, &state.user_id).await?;
+    }
+
+    // Also ensure that onboarding has happened
+    check_onboarded(state).await
+}
+
+/// Ensures the user has completed staff onboarding
+async fn check_onboarded(state: &RPCHandle) -> Result<(), Error> {
+    let onboard_state = sqlx::query!(
+        "SELECT staff, staff_onboard_state FROM users WHERE user_id = $1",
+        &state.user_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    if onboard_state.staff_onboard_state != "completed" {
+        return Err(RPCFailure::onboarding_required(
+            "You need to complete onboarding in order to use RPC!",
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Sets the state of a log entry outside of the method's transaction
+pub(super) async fn set_log_state(pool: &PgPool, log_id: Uuid, state: &str) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE rpc_logs SET state = $1 WHERE id = $2",
+        state,
+        log_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Queues the method's messages and marks its log entry as successful
+///
+/// This runs in the method's transaction so the log and the changes can never disagree
+pub(super) async fn record_success(
+    tx: &mut Transaction<'_, Postgres>,
+    log_id: Uuid,
+    effects: &mut RPCEffects,
+) -> Result<(), Error> {
+    // Messages and owner webhooks are queued in the same transaction so they are only delivered if the method is committed
+    effects.enqueue_outbox(tx).await?;
+
+    let (before_image, reverts) = effects.revert_info()?;
+    let after_image = effects.after_image(tx).await?;
+
+    sqlx::query!(
+        "UPDATE rpc_logs SET state = $1, before_image = $2, after_image = $3, reverts = $4 WHERE id = $5",
+        "success",
+        before_image,
+        after_image,
+        reverts,
+        log_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if let Some(reverts) = reverts {
+        sqlx::query!(
+            "UPDATE rpc_logs SET reverted_by = $1 WHERE id = $2",
+            log_id,
+            reverts
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Saved with every bot changed by a bulk method, as these skip the usual review rules
+const BULK_AUDIT_NOTE: &str = "Bulk triage: the claim and minimum test time checks were skipped";
+
+/// A pending bot being acted on by a bulk method
+struct BulkBot {
+    bot_id: String,
+    claimed_by: Option<String>,
+    /// The user to ping about the bot
+    owner: String,
+}
+
+impl BulkBot {
+    /// The reason given for this bot in ``reasons``, otherwise the reason template filled in for this bot
+    fn reason(&self, template: &str, reasons: &Option<HashMap<String, String>>) -> String {
+        if let Some(reason) = reasons.as_ref().and_then(|r| r.get(&self.bot_id)) {
+            return reason.clone();
+        }
+
+        template
+            .replace("{bot_id}", &self.bot_id)
+            .replace("{bot}", &format!("<@{}>", self.bot_id))
+            .replace("{owner}", &format!("<@{}>", self.owner))
+    }
+}
+
+/// Fetches and locks the bots of a bulk method, failing the whole call if any of them is not pending review
+async fn bulk_pending_bots(
+    state: &RPCHandle,
+    tx: &mut Transaction<'_, Postgres>,
+    bot_ids: &[String],
+    reasons: &Option<HashMap<String, String>>,
+) -> Result<Vec<BulkBot>, Error> {
+    if let Some(reasons) = reasons {
+        for bot_id in reasons.keys() {
+            if !bot_ids.contains(bot_id) {
+                return Err(RPCFailure::invalid_field(
+                    "reasons",
+                    format!("<@{}> is not one of the bots being acted on", bot_id),
+                )
+                .into());
+            }
+        }
+    }
+
+    let mut bots: Vec<BulkBot> = Vec::new();
+
+    for bot_id in bot_ids {
+        // A bot listed twice is only acted on once
+        if bots.iter().any(|b| &b.bot_id == bot_id) {
+            continue;
+        }
+
+        let rec = sqlx::query!(
+            "SELECT type, claimed_by FROM bots WHERE bot_id = $1 FOR UPDATE",
+            bot_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let rec = match rec {
+            Some(rec) => rec,
+            None => {
+                return Err(RPCFailure::not_found(format!("<@{}> does not exist", bot_id)).into())
+            }
+        };
+
+        if rec.r#type != "pending" {
+            return Err(RPCFailure::invalid_field(
+                "bot_ids",
+                format!("<@{}> is not pending review", bot_id),
+            )
+            .into());
+        }
+
+        bots.push(BulkBot {
+            bot_id: bot_id.clone(),
+            claimed_by: rec.claimed_by,
+            owner: crate::impls::utils::resolve_ping_user(bot_id, &state.pool).await?,
+        });
+    }
+
+    Ok(bots)
+}
+
+/// The one mod log message sent for a bulk method, instead of one per bot
+fn bulk_summary(
+    state: &RPCHandle,
+    title: &str,
+    bots: &[BulkBot],
+    template: &str,
+    reasons: &Option<HashMap<String, String>>,
+) -> CreateMessage {
+    let mut description = String::new();
+
+    for (i, bot) in bots.iter().enumerate() {
+        let line = format!("<@{}>: {}\n", bot.bot_id, bot.reason(template, reasons));
+
+        // Embed descriptions can be at most 4096 characters long
+        if description.chars().count() + line.chars().count() > 4000 {
+            description.push_str(&format!("...and {} more", bots.len() - i));
+            break;
+        }
+
+        description.push_str(&line);
+    }
+
+    let mut owners = bots.iter().map(|b| b.owner.as_str()).collect::<Vec<_>>();
+    owners.sort_unstable();
+    owners.dedup();
+
+    let mut pings = String::new();
+
+    for owner in owners {
+        let ping = format!("<@!{}> ", owner);
+
+        // Messages can be at most 2000 characters long
+        if pings.len() + ping.len() > 2000 {
+            break;
+        }
+
+        pings.push_str(&ping);
+    }
+
+    CreateMessage::new().content(pings).embed(
+        CreateEmbed::default()
+            .title(title)
+            .description(description)
+            .field("Moderator", format!("<@!{}>", state.user_id), true)
+            .field("Bots", bots.len().to_string(), true)
+            .field("Note", BULK_AUDIT_NOTE, false),
+    )
+}
+
 pub enum RPCSuccess {
     NoContent,
     Content(String),
+    DryRun(RPCDiff),
+    /// The method needs a quorum and was saved as a pending action with this ID
+    PendingApproval(String),
 }
 
 impl RPCSuccess {
@@ -1244,3 +1810,39 @@ impl RPCSuccess {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot() -> BulkBot {
+        BulkBot {
+            bot_id: "1".to_string(),
+            claimed_by: None,
+            owner: "2".to_string(),
+        }
+    }
+
+    #[test]
+    fn bulk_reason_fills_template() {
+        assert_eq!(
+            bot().reason("{bot} ({bot_id}) by {owner} is spam", &None),
+            "<@1> (1) by <@2> is spam"
+        );
+        assert_eq!(bot().reason("Spam", &None), "Spam");
+    }
+
+    #[test]
+    fn bulk_reason_prefers_reasons() {
+        let reasons = Some(HashMap::from([(
+            "1".to_string(),
+            "Broken {bot}".to_string(),
+        )]));
+
+        assert_eq!(bot().reason("Spam", &reasons), "Broken {bot}");
+
+        let other = Some(HashMap::from([("3".to_string(), "Broken".to_string())]));
+
+        assert_eq!(bot().reason("Spam", &other), "Spam");
+    }
+}
@@ -0,0 +1,230 @@
+use std::num::NonZeroU64;
+
+use log::error;
+use poise::serenity_prelude::{CreateMessage, GuildId, RoleId, UserId};
+use schemars::JsonSchema;
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, types::Uuid, Postgres, Transaction};
+use ts_rs::TS;
+
+use super::events::RPCEvent;
+use super::revert::BeforeImage;
+use crate::{impls, impls::notifications::Notification, impls::webhooks, Error};
+
+/// A Discord side effect of an RPC method
+///
+/// These are put in an outbox while the method runs and are only dispatched once the database changes of
+/// the method have been committed. Messages are not effects, they go through the ``notifications`` outbox
+pub enum RPCEffect {
+    AddRole {
+        user_id: UserId,
+        role_id: NonZeroU64,
+        reason: String,
+    },
+    Kick {
+        user_id: UserId,
+        reason: String,
+    },
+}
+
+/// Collects everything a method changes while it runs
+#[derive(Default)]
+pub struct RPCEffects {
+    rows_touched: Vec<RowsTouched>,
+    messages: Vec<Notification>,
+    effects: Vec<RPCEffect>,
+    events: Vec<RPCEvent>,
+    before_image: Option<BeforeImage>,
+    reverts: Option<Uuid>,
+}
+
+impl RPCEffects {
+    /// Records the rows changed by a query
+    pub fn touched(&mut self, table: &str, res: PgQueryResult) {
+        self.rows_touched.push(RowsTouched {
+            table: table.to_string(),
+            rows: res.rows_affected(),
+        });
+    }
+
+    /// Saves the state the method is about to overwrite so it can be reverted later
+    pub fn before_image(&mut self, image: BeforeImage) {
+        self.before_image = Some(image);
+    }
+
+    /// Marks this method as reverting an earlier ``rpc_logs`` entry
+    pub fn reverts(&mut self, log_id: Uuid) {
+        self.reverts = Some(log_id);
+    }
+
+    /// Returns the saved before-image (as stored in ``rpc_logs``) and the log this method reverts, if any
+    pub fn revert_info(&self) -> Result<(Option<serde_json::Value>, Option<Uuid>), Error> {
+        let image = match &self.before_image {
+            Some(image) => Some(serde_json::to_value(image)?),
+            None => None,
+        };
+
+        Ok((image, self.reverts))
+    }
+
+    /// Captures the state the method left behind, to compare against when it is reverted
+    pub async fn after_image(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<serde_json::Value>, Error> {
+        match &self.before_image {
+            Some(image) => Ok(Some(serde_json::to_value(image.current(tx).await?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Queues a message to be posted
+    pub fn message(&mut self, channel_id: NonZeroU64, message: CreateMessage) {
+        self.messages.push(Notification::new(channel_id, message));
+    }
+
+    /// Queues an event to be published to the ``/events`` feed and, for review outcomes, to the owner webhook
+    pub fn event(&mut self, event: RPCEvent) {
+        self.events.push(event);
+    }
+
+    /// Queues a role to be added to a member of the main server
+    pub fn add_role(&mut self, user_id: UserId, role_id: NonZeroU64, reason: &str) {
+        self.effects.push(RPCEffect::AddRole {
+            user_id,
+            role_id,
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Queues a member of the main server to be kicked
+    pub fn kick(&mut self, user_id: UserId, reason: String) {
+        self.effects.push(RPCEffect::Kick { user_id, reason });
+    }
+
+    /// Returns what would change if the method was committed
+    pub fn diff(self) -> RPCDiff {
+        let mut diff = RPCDiff {
+            rows_touched: self.rows_touched,
+            roles_changed: Vec::new(),
+            messages: self
+                .messages
+                .iter()
+                .map(|n| PlannedMessage {
+                    channel_id: n.channel_id.to_string(),
+                    message: serde_json::to_value(&n.message).unwrap_or_default(),
+                })
+                .collect(),
+            kicks: Vec::new(),
+        };
+
+        for effect in self.effects {
+            match effect {
+                RPCEffect::AddRole {
+                    user_id, role_id, ..
+                } => diff.roles_changed.push(RoleChange {
+                    user_id: user_id.to_string(),
+                    role_id: role_id.to_string(),
+                    added: true,
+                }),
+                RPCEffect::Kick { user_id, .. } => diff.kicks.push(user_id.to_string()),
+            }
+        }
+
+        diff
+    }
+
+    /// Writes all queued messages to the ``notifications`` outbox and all owner-facing events to the
+    /// ``bot_webhook_deliveries`` outbox as part of the method's transaction
+    pub async fn enqueue_outbox(
+        &mut self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), Error> {
+        for notification in self.messages.drain(..) {
+            notification.enqueue(&mut *tx).await?;
+        }
+
+        for event in &self.events {
+            if let Some(bot_id) = webhooks::owner_event(event) {
+                webhooks::enqueue(&mut *tx, bot_id, event).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dispatches all queued side effects (the outbox of the method), in the order they were queued
+    ///
+    /// This must only be called after the changes of the method have been committed. Failures are
+    /// logged and do not stop the remaining effects from being dispatched
+    pub async fn dispatch(self, cache_http: &impls::cache::CacheHttpImpl) {
+        for event in self.events {
+            event.publish();
+        }
+
+        for effect in self.effects {
+            let res = match effect {
+                RPCEffect::AddRole {
+                    user_id,
+                    role_id,
+                    reason,
+                } => {
+                    cache_http
+                        .http
+                        .add_member_role(
+                            GuildId(crate::config::CONFIG.servers.main),
+                            user_id,
+                            RoleId(role_id),
+                            Some(&reason),
+                        )
+                        .await
+                }
+                RPCEffect::Kick { user_id, reason } => {
+                    GuildId(crate::config::CONFIG.servers.main)
+                        .kick_with_reason(cache_http, user_id, &reason)
+                        .await
+                }
+            };
+
+            if let Err(e) = res {
+                error!("Failed to dispatch RPC side effect: {}", e);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCRowsTouched.ts")]
+#[schemars(rename = "RPCRowsTouched")]
+pub struct RowsTouched {
+    pub table: String,
+    pub rows: u64,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCRoleChange.ts")]
+#[schemars(rename = "RPCRoleChange")]
+pub struct RoleChange {
+    pub user_id: String,
+    pub role_id: String,
+    pub added: bool,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCPlannedMessage.ts")]
+#[schemars(rename = "RPCPlannedMessage")]
+pub struct PlannedMessage {
+    pub channel_id: String,
+    #[ts(type = "any")]
+    pub message: serde_json::Value,
+}
+
+/// What a dry run of a method would have changed
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCDiff.ts")]
+pub struct RPCDiff {
+    pub rows_touched: Vec<RowsTouched>,
+    pub roles_changed: Vec<RoleChange>,
+    pub messages: Vec<PlannedMessage>,
+    pub kicks: Vec<String>,
+}
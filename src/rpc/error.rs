@@ -0,0 +1,178 @@
+use std::fmt;
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use strum_macros::EnumIter;
+use ts_rs::TS;
+
+/// A stable, machine-readable error code. The frontend should branch on these instead of the message
+#[derive(Serialize, TS, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, EnumIter)]
+#[ts(export, export_to = ".generated/RPCErrorCode.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum RPCErrorCode {
+    /// The client is speaking a protocol version the server does not support
+    InvalidProtocol,
+    /// The RPC identity is unknown, expired or belongs to another user
+    InvalidIdentity,
+    /// The request signature is missing, wrong, too old or was already used
+    InvalidSignature,
+    /// The RPC identity has no uses left
+    UsageQuotaExceeded,
+    /// The RPC identity was not unlocked for this method
+    MethodNotAllowed,
+    /// The RPC identity is bound to other bots/teams or to smaller argument values
+    OutOfScope,
+    /// The user could not be found (or the API token is wrong)
+    UserNotFound,
+    /// The endpoint can only be used by staff
+    StaffOnly,
+    /// The user does not have the permissions needed for this method
+    PermissionDenied,
+    /// The user must complete staff onboarding first
+    OnboardingRequired,
+    /// Too many requests, try again later
+    RateLimited,
+    /// The bot/team etc. that the method acts on does not exist
+    NotFound,
+    /// One of the arguments to the method is invalid
+    InvalidArgument,
+    /// The bot/team etc. is not in a state where this method can be used (e.g. not pending review)
+    InvalidState,
+    /// Not applied because another call in the same all-or-nothing batch failed
+    Aborted,
+    /// A database error occurred
+    Database,
+    /// Discord returned an error
+    Discord,
+    /// The method failed for a reason that has no more specific code
+    MethodFailed,
+    /// An unexpected internal error
+    Internal,
+}
+
+impl RPCErrorCode {
+    /// Whether retrying the same request later may succeed
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited | Self::Database | Self::Discord | Self::Internal
+        )
+    }
+}
+
+/// The JSON body returned for every failed RPC request
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCError.ts")]
+pub struct RPCError {
+    pub code: RPCErrorCode,
+    pub message: String,
+    pub retryable: bool,
+    #[ts(type = "any")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// An error with a stable code that can be returned from inside ``RPCMethod::handle``
+///
+/// Any other error returned from a method is reported as ``RPCErrorCode::MethodFailed``
+#[derive(Debug)]
+pub struct RPCFailure {
+    pub code: RPCErrorCode,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl RPCFailure {
+    pub fn new(code: RPCErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self::new(RPCErrorCode::PermissionDenied, message)
+    }
+
+    pub fn onboarding_required(message: impl Into<String>) -> Self {
+        Self::new(RPCErrorCode::OnboardingRequired, message)
+    }
+
+    /// A rate limit error, ``retry_after`` is sent to the client in the ``Retry-After`` header
+    pub fn rate_limited_for(message: impl Into<String>, retry_after: Duration) -> Self {
+        Self {
+            code: RPCErrorCode::RateLimited,
+            message: message.into(),
+            details: Some(serde_json::json!({
+                "retry_after": retry_after.as_secs().max(1),
+            })),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(RPCErrorCode::NotFound, message)
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(RPCErrorCode::InvalidArgument, message)
+    }
+
+    /// An invalid argument, ``field`` is sent to the client so it can point at the bad input
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: RPCErrorCode::InvalidArgument,
+            message: message.into(),
+            details: Some(serde_json::json!({
+                "field": field,
+            })),
+        }
+    }
+
+    /// The call is outside the scope of the RPC identity, ``field`` is the argument that is out of scope
+    pub fn out_of_scope(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: RPCErrorCode::OutOfScope,
+            message: message.into(),
+            details: Some(serde_json::json!({
+                "field": field,
+            })),
+        }
+    }
+
+    pub fn invalid_state(message: impl Into<String>) -> Self {
+        Self::new(RPCErrorCode::InvalidState, message)
+    }
+}
+
+impl fmt::Display for RPCFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RPCFailure {}
+
+impl From<&crate::Error> for RPCError {
+    fn from(e: &crate::Error) -> Self {
+        let (code, details) = if let Some(failure) = e.downcast_ref::<RPCFailure>() {
+            (failure.code, failure.details.clone())
+        } else if let Some(sqlx_err) = e.downcast_ref::<sqlx::Error>() {
+            match sqlx_err {
+                sqlx::Error::RowNotFound => (RPCErrorCode::NotFound, None),
+                _ => (RPCErrorCode::Database, None),
+            }
+        } else if e.downcast_ref::<serenity::Error>().is_some() {
+            (RPCErrorCode::Discord, None)
+        } else {
+            (RPCErrorCode::MethodFailed, None)
+        };
+
+        RPCError {
+            code,
+            message: e.to_string(),
+            retryable: code.retryable(),
+            details,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use log::info;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use ts_rs::TS;
+
+/// How many events a slow subscriber can fall behind by before it starts missing them
+const EVENT_BUFFER: usize = 256;
+
+static EVENTS: Lazy<broadcast::Sender<RPCEvent>> = Lazy::new(|| {
+    info!("RPC event feed initialized");
+
+    broadcast::channel(EVENT_BUFFER).0
+});
+
+/// A moderation event pushed to the staff panel over ``/events``
+///
+/// These are the same events that are posted to the mod logs
+#[derive(Serialize, TS, Clone, Debug)]
+#[ts(export, export_to = ".generated/RPCEvent.ts")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RPCEvent {
+    BotClaimed {
+        bot_id: String,
+        by: String,
+        force: bool,
+    },
+    BotUnclaimed {
+        bot_id: String,
+        by: String,
+        reason: String,
+    },
+    BotApproved {
+        bot_id: String,
+        by: String,
+        reason: String,
+    },
+    BotDenied {
+        bot_id: String,
+        by: String,
+        reason: String,
+    },
+    BotUnverified {
+        bot_id: String,
+        by: String,
+        reason: String,
+    },
+    /// A denied bot was moved back into the queue by staff or by one of its owners
+    BotResubmitted {
+        bot_id: String,
+        by: String,
+        reason: String,
+        by_owner: bool,
+    },
+    PremiumChanged {
+        bot_id: String,
+        /// ``None`` if premium was removed by a task (e.g. the subscription expired)
+        by: Option<String>,
+        premium: bool,
+    },
+    VoteBanChanged {
+        bot_id: String,
+        by: String,
+        vote_banned: bool,
+    },
+    UptimeWarning {
+        bot_id: String,
+    },
+    BotJoined {
+        bot_id: String,
+        name: String,
+    },
+}
+
+impl RPCEvent {
+    /// The SSE event name, the same as the ``type`` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BotClaimed { .. } => "bot_claimed",
+            Self::BotUnclaimed { .. } => "bot_unclaimed",
+            Self::BotApproved { .. } => "bot_approved",
+            Self::BotDenied { .. } => "bot_denied",
+            Self::BotUnverified { .. } => "bot_unverified",
+            Self::BotResubmitted { .. } => "bot_resubmitted",
+            Self::PremiumChanged { .. } => "premium_changed",
+            Self::VoteBanChanged { .. } => "vote_ban_changed",
+            Self::UptimeWarning { .. } => "uptime_warning",
+            Self::BotJoined { .. } => "bot_joined",
+        }
+    }
+
+    /// Sends the event to everyone subscribed to the feed
+    ///
+    /// Only publish events for changes that have been committed
+    pub fn publish(self) {
+        // This only fails if nobody is subscribed, which is fine
+        let _ = EVENTS.send(self);
+    }
+}
+
+/// Subscribes to all events published from now on
+pub fn subscribe() -> broadcast::Receiver<RPCEvent> {
+    EVENTS.subscribe()
+}
@@ -0,0 +1,547 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
+use log::info;
+use moka::future::Cache;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use super::core::RPCMethod;
+use super::error::RPCFailure;
+use crate::Error;
+
+/// How long a freshly minted RPC identity stays valid for
+pub const KEYCHAIN_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Read-through cache in front of the ``rpc_keychain`` table, keyed by key ID
+///
+/// This only ever holds what was last read from the database, the table is always the source of truth
+static KEYCHAIN_CACHE: Lazy<Cache<String, KeychainData>> = Lazy::new(|| {
+    info!("RPCKeychain cache initialized");
+
+    Cache::builder()
+        // Keep this short so revocations on other instances are picked up quickly
+        .time_to_live(Duration::from_secs(30))
+        .build()
+});
+
+/// An RPC identity as stored in the ``rpc_keychain`` table
+///
+/// Only the SHA-256 hash of the identity and its public key ID are stored, the identity itself is only sent to the user
+#[derive(Clone)]
+pub struct KeychainData {
+    pub user_id: String,
+    pub allowed_methods: Vec<String>,
+    pub max_uses: i32,
+    pub used: i32,
+    pub reason: String,
+    pub expires_at: DateTime<Utc>,
+    pub scope: KeychainScope,
+    /// The public ID requests signed with this identity are sent with, see ``signing::signing_key``
+    pub key_id: String,
+}
+
+/// Limits what an RPC identity can touch, on top of ``allowed_methods``
+///
+/// Stored as JSON in ``rpc_keychain.scope``, an empty scope does not restrict anything
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct KeychainScope {
+    /// The bots the identity may act on
+    #[serde(default)]
+    pub bot_ids: Vec<String>,
+    /// The teams the identity may act on, this includes bots owned by these teams
+    #[serde(default)]
+    pub team_ids: Vec<String>,
+    /// The largest ``count`` the identity may pass (e.g. to ``BotVoteCountSet``)
+    #[serde(default)]
+    pub max_count: Option<i64>,
+    /// The largest ``time_period_hours`` the identity may pass (e.g. to ``BotPremiumAdd``)
+    #[serde(default)]
+    pub max_time_period_hours: Option<i64>,
+}
+
+impl KeychainScope {
+    /// Whether the identity is bound to specific bots or teams
+    pub fn is_bound(&self) -> bool {
+        !self.bot_ids.is_empty() || !self.team_ids.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.is_bound() && self.max_count.is_none() && self.max_time_period_hours.is_none()
+    }
+
+    /// A short human readable summary of the scope
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "Unrestricted".to_string();
+        }
+
+        let mut parts = Vec::new();
+
+        if !self.bot_ids.is_empty() {
+            parts.push(format!("Bots: {}", self.bot_ids.join(", ")));
+        }
+
+        if !self.team_ids.is_empty() {
+            parts.push(format!("Teams: {}", self.team_ids.join(", ")));
+        }
+
+        if let Some(max_count) = self.max_count {
+            parts.push(format!("Max count: {}", max_count));
+        }
+
+        if let Some(max_hours) = self.max_time_period_hours {
+            parts.push(format!("Max time period: {} hours", max_hours));
+        }
+
+        parts.join("; ")
+    }
+
+    /// Checks that a method call stays within this scope
+    ///
+    /// A bound identity can only call methods that act on a bot or team, methods acting on
+    /// everything at once (like ``BotVoteResetAll``) are always out of scope for it
+    pub async fn check(&self, pool: &PgPool, method: &RPCMethod) -> Result<(), Error> {
+        let value = serde_json::to_value(method)?;
+        let args = value.get(method.to_string()).cloned().unwrap_or_default();
+
+        for (field, bot_id) in self.check_args(&args)? {
+            if !self.bot_in_teams(pool, &bot_id).await? {
+                return Err(RPCFailure::out_of_scope(
+                    field,
+                    format!("This RPC identity cannot act on <@{}>", bot_id),
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The part of ``check`` that needs no database
+    ///
+    /// Returns the bots (and the field naming them) that are not in ``bot_ids``, these are only
+    /// in scope if one of the teams of the scope owns them
+    fn check_args(&self, args: &Value) -> Result<Vec<(&'static str, String)>, Error> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        for (field, max) in [
+            ("count", self.max_count),
+            ("time_period_hours", self.max_time_period_hours),
+        ] {
+            if let (Some(max), Some(v)) = (max, args.get(field).and_then(|v| v.as_i64())) {
+                if v > max {
+                    return Err(RPCFailure::out_of_scope(
+                        field,
+                        format!(
+                            "This RPC identity only allows a {} of at most {}",
+                            field, max
+                        ),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        if !self.is_bound() {
+            return Ok(Vec::new());
+        }
+
+        let mut targeted = false;
+
+        for field in ["team_id", "new_team"] {
+            if let Some(team_id) = args.get(field).and_then(|v| v.as_str()) {
+                targeted = true;
+
+                if !self.team_ids.iter().any(|t| t == team_id) {
+                    return Err(RPCFailure::out_of_scope(
+                        field,
+                        "This RPC identity cannot act on this team",
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut unlisted = Vec::new();
+
+        if let Some(bot_id) = args.get("bot_id").and_then(|v| v.as_str()) {
+            targeted = true;
+
+            if !self.bot_ids.iter().any(|b| b == bot_id) {
+                unlisted.push(("bot_id", bot_id.to_string()));
+            }
+        }
+
+        if let Some(bot_ids) = args.get("bot_ids").and_then(|v| v.as_array()) {
+            targeted = true;
+
+            for bot_id in bot_ids.iter().filter_map(|v| v.as_str()) {
+                if !self.bot_ids.iter().any(|b| b == bot_id) {
+                    unlisted.push(("bot_ids", bot_id.to_string()));
+                }
+            }
+        }
+
+        if !targeted {
+            return Err(RPCFailure::out_of_scope(
+                "method",
+                "This RPC identity is bound to specific bots or teams and cannot call this method",
+            )
+            .into());
+        }
+
+        Ok(unlisted)
+    }
+
+    /// Whether the bot is owned by one of the teams of this scope
+    async fn bot_in_teams(&self, pool: &PgPool, bot_id: &str) -> Result<bool, Error> {
+        if self.team_ids.is_empty() {
+            return Ok(false);
+        }
+
+        let rec = sqlx::query!(
+            "SELECT team_owner::text FROM bots WHERE bot_id = $1",
+            bot_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match rec.and_then(|r| r.team_owner) {
+            Some(team_owner) => self.team_ids.contains(&team_owner),
+            None => false,
+        })
+    }
+}
+
+/// The remaining budget of an identity after a use has been consumed
+pub struct KeychainQuota {
+    pub remaining_uses: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// An active identity as listed to staff, the identity itself is never shown
+pub struct KeychainSession {
+    pub fingerprint: String,
+    pub data: KeychainData,
+}
+
+/// Number of hex characters of the identity hash used as its fingerprint
+pub const FINGERPRINT_LEN: usize = 16;
+
+/// Returns the fingerprint of an identity hash, this is enough to tell sessions apart but not to use them
+pub fn fingerprint(identity_hash: &str) -> String {
+    identity_hash.chars().take(FINGERPRINT_LEN).collect()
+}
+
+/// Hashes an RPC identity for storage, the hash is only used to tell identities apart and is never sent by clients
+pub fn hash_identity(identity: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, identity.as_bytes());
+    HEXLOWER.encode(digest.as_ref())
+}
+
+impl KeychainData {
+    /// Fetches an identity by its key ID, returning ``None`` if it does not exist or has expired
+    pub async fn get(pool: &PgPool, key_id: &str) -> Result<Option<KeychainData>, Error> {
+        if let Some(data) = KEYCHAIN_CACHE.get(key_id) {
+            if data.expires_at > Utc::now() {
+                return Ok(Some(data));
+            }
+
+            KEYCHAIN_CACHE.invalidate(key_id).await;
+            return Ok(None);
+        }
+
+        let rec = sqlx::query!(
+            "SELECT user_id, allowed_methods, max_uses, used, reason, expires_at, scope, key_id FROM rpc_keychain WHERE key_id = $1 AND expires_at > NOW()",
+            key_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let rec = match rec {
+            Some(rec) => rec,
+            None => return Ok(None),
+        };
+
+        let data = KeychainData {
+            user_id: rec.user_id,
+            allowed_methods: rec.allowed_methods,
+            max_uses: rec.max_uses,
+            used: rec.used,
+            reason: rec.reason,
+            expires_at: rec.expires_at,
+            scope: serde_json::from_value(rec.scope)?,
+            key_id: rec.key_id,
+        };
+
+        KEYCHAIN_CACHE
+            .insert(data.key_id.clone(), data.clone())
+            .await;
+
+        Ok(Some(data))
+    }
+
+    /// Saves a new identity to the keychain
+    pub async fn insert(&self, pool: &PgPool, identity: &str) -> Result<(), Error> {
+        // Clear out expired identities while we're here
+        sqlx::query!("DELETE FROM rpc_keychain WHERE expires_at < NOW()")
+            .execute(pool)
+            .await?;
+
+        sqlx::query!(
+            "INSERT INTO rpc_keychain (identity_hash, user_id, allowed_methods, max_uses, used, reason, expires_at, scope, key_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            hash_identity(identity),
+            &self.user_id,
+            &self.allowed_methods,
+            self.max_uses,
+            self.used,
+            &self.reason,
+            self.expires_at,
+            serde_json::to_value(&self.scope)?,
+            &self.key_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes an identity from the keychain, returning whether it existed
+    pub async fn remove(pool: &PgPool, identity: &str) -> Result<bool, Error> {
+        let recs = sqlx::query!(
+            "DELETE FROM rpc_keychain WHERE identity_hash = $1 RETURNING key_id",
+            hash_identity(identity)
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::invalidate(recs.into_iter().map(|r| r.key_id).collect()).await > 0)
+    }
+
+    /// Atomically consumes one use of an identity
+    ///
+    /// This is a single compare-and-decrement in the database so concurrent requests can never go
+    /// past ``max_uses``. Returns ``None`` if the identity has no uses left (or has expired)
+    pub async fn consume_use(pool: &PgPool, key_id: &str) -> Result<Option<KeychainQuota>, Error> {
+        let rec = sqlx::query!(
+            "UPDATE rpc_keychain SET used = used + 1 WHERE key_id = $1 AND used < max_uses AND expires_at > NOW() RETURNING max_uses - used AS \"remaining_uses!\", expires_at",
+            key_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        // The cached copy has a stale use count now
+        KEYCHAIN_CACHE.invalidate(key_id).await;
+
+        Ok(rec.map(|r| KeychainQuota {
+            remaining_uses: r.remaining_uses,
+            expires_at: r.expires_at,
+        }))
+    }
+
+    /// Returns the number of identities that have not yet expired
+    pub async fn active_count(pool: &PgPool) -> Result<i64, Error> {
+        let rec = sqlx::query!("SELECT COUNT(*) FROM rpc_keychain WHERE expires_at > NOW()")
+            .fetch_one(pool)
+            .await?;
+
+        Ok(rec.count.unwrap_or_default())
+    }
+
+    /// Lists all identities that have not yet expired, newest first
+    pub async fn sessions(pool: &PgPool) -> Result<Vec<KeychainSession>, Error> {
+        let recs = sqlx::query!(
+            "SELECT identity_hash, user_id, allowed_methods, max_uses, used, reason, expires_at, scope, key_id FROM rpc_keychain WHERE expires_at > NOW() ORDER BY expires_at DESC"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut sessions = Vec::new();
+
+        for rec in recs {
+            sessions.push(KeychainSession {
+                fingerprint: fingerprint(&rec.identity_hash),
+                data: KeychainData {
+                    user_id: rec.user_id,
+                    allowed_methods: rec.allowed_methods,
+                    max_uses: rec.max_uses,
+                    used: rec.used,
+                    reason: rec.reason,
+                    expires_at: rec.expires_at,
+                    scope: serde_json::from_value(rec.scope)?,
+                    key_id: rec.key_id,
+                },
+            });
+        }
+
+        Ok(sessions)
+    }
+
+    /// Drops revoked identities from the cache so they stop working immediately on this instance
+    async fn invalidate(key_ids: Vec<String>) -> u64 {
+        let count = key_ids.len() as u64;
+
+        for key_id in key_ids {
+            KEYCHAIN_CACHE.invalidate(&key_id).await;
+        }
+
+        count
+    }
+
+    /// Revokes the identity with this fingerprint, returning whether it existed
+    pub async fn revoke_fingerprint(pool: &PgPool, fingerprint: &str) -> Result<bool, Error> {
+        if fingerprint.len() != FINGERPRINT_LEN
+            || !fingerprint.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(format!(
+                "A fingerprint is {} hexadecimal characters long",
+                FINGERPRINT_LEN
+            )
+            .into());
+        }
+
+        let recs = sqlx::query!(
+            "DELETE FROM rpc_keychain WHERE LEFT(identity_hash, $1) = $2 RETURNING key_id",
+            FINGERPRINT_LEN as i32,
+            fingerprint.to_lowercase()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::invalidate(recs.into_iter().map(|r| r.key_id).collect()).await > 0)
+    }
+
+    /// Revokes every identity of a user, returning how many were revoked
+    pub async fn revoke_user(pool: &PgPool, user_id: &str) -> Result<u64, Error> {
+        let recs = sqlx::query!(
+            "DELETE FROM rpc_keychain WHERE user_id = $1 RETURNING key_id",
+            user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::invalidate(recs.into_iter().map(|r| r.key_id).collect()).await)
+    }
+
+    /// Revokes every identity, returning how many of them had not yet expired
+    pub async fn revoke_all(pool: &PgPool) -> Result<u64, Error> {
+        let recs =
+            sqlx::query!("DELETE FROM rpc_keychain RETURNING expires_at > NOW() AS \"active!\"")
+                .fetch_all(pool)
+                .await?;
+
+        KEYCHAIN_CACHE.invalidate_all();
+
+        Ok(recs.iter().filter(|r| r.active).count() as u64)
+    }
+
+    /// Checks the database (never the cache) that an identity has not been revoked or expired
+    ///
+    /// The cache of another instance may still hold a revoked identity, so anything that does not go
+    /// through ``consume_use`` must check this before trusting a cached identity
+    pub async fn is_active(pool: &PgPool, key_id: &str) -> Result<bool, Error> {
+        let rec = sqlx::query!(
+            "SELECT COUNT(*) FROM rpc_keychain WHERE key_id = $1 AND expires_at > NOW()",
+            key_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        if rec.count.unwrap_or_default() == 0 {
+            KEYCHAIN_CACHE.invalidate(key_id).await;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bound() -> KeychainScope {
+        KeychainScope {
+            bot_ids: vec!["1".to_string()],
+            team_ids: vec!["team".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_scope_allows_everything() {
+        let scope = KeychainScope::default();
+
+        assert!(scope
+            .check_args(&json!({ "reason": "test" }))
+            .unwrap()
+            .is_empty());
+        assert!(scope
+            .check_args(&json!({ "count": 1000 }))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn limits() {
+        let scope = KeychainScope {
+            max_count: Some(10),
+            max_time_period_hours: Some(24),
+            ..Default::default()
+        };
+
+        assert!(scope.check_args(&json!({ "count": 10 })).is_ok());
+        assert!(scope.check_args(&json!({ "count": 11 })).is_err());
+        assert!(scope
+            .check_args(&json!({ "time_period_hours": 24 }))
+            .is_ok());
+        assert!(scope
+            .check_args(&json!({ "time_period_hours": 25 }))
+            .is_err());
+        // Limits alone do not bind the identity to any bot
+        assert!(scope.check_args(&json!({ "reason": "test" })).is_ok());
+    }
+
+    #[test]
+    fn bound_scope_teams() {
+        let scope = bound();
+
+        assert!(scope.check_args(&json!({ "team_id": "team" })).is_ok());
+        assert!(scope.check_args(&json!({ "team_id": "other" })).is_err());
+        assert!(scope
+            .check_args(&json!({ "bot_id": "1", "new_team": "other" }))
+            .is_err());
+    }
+
+    #[test]
+    fn bound_scope_bots() {
+        let scope = bound();
+
+        assert!(scope
+            .check_args(&json!({ "bot_id": "1" }))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            scope.check_args(&json!({ "bot_id": "2" })).unwrap(),
+            vec![("bot_id", "2".to_string())]
+        );
+        assert_eq!(
+            scope
+                .check_args(&json!({ "bot_ids": ["1", "2", "3"] }))
+                .unwrap(),
+            vec![("bot_ids", "2".to_string()), ("bot_ids", "3".to_string())]
+        );
+    }
+
+    #[test]
+    fn bound_scope_rejects_untargeted_methods() {
+        assert!(bound().check_args(&json!({ "reason": "test" })).is_err());
+    }
+}
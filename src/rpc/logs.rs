@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use futures_util::TryStreamExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, PgPool};
+use tokio::sync::mpsc;
+use ts_rs::TS;
+
+use super::core::RPCMethod;
+use super::error::RPCFailure;
+use crate::Error;
+
+/// The most entries returned in one page
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Filters for ``rpc_logs``, every filter is optional
+#[derive(Deserialize, TS, Default, Clone)]
+#[ts(export, export_to = ".generated/RPCLogFilter.ts")]
+pub struct LogFilter {
+    /// The staff member who made the call
+    pub user_id: Option<String>,
+    pub method: Option<String>,
+    /// The bot the call acted on, including bulk calls that list it in ``bot_ids``
+    pub bot_id: Option<String>,
+    /// ``pending``, ``success``, ``scheduled``, ``cancelled`` or ``error`` (any failed call)
+    pub state: Option<String>,
+    #[ts(type = "string | null")]
+    pub after: Option<DateTime<Utc>>,
+    #[ts(type = "string | null")]
+    pub before: Option<DateTime<Utc>>,
+    /// The ``next_cursor`` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCLogEntry.ts")]
+#[schemars(rename = "RPCLogEntry")]
+pub struct LogEntry {
+    #[schemars(with = "Uuid")]
+    pub id: String,
+    pub method: String,
+    pub user_id: String,
+    #[ts(type = "any")]
+    #[schemars(with = "RPCMethod")]
+    pub data: serde_json::Value,
+    pub state: String,
+    pub approved_by: Option<String>,
+    #[schemars(with = "Option<Uuid>")]
+    pub batch_id: Option<String>,
+    #[schemars(with = "Option<Uuid>")]
+    pub reverted_by: Option<String>,
+    #[ts(type = "string")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCLogPage.ts")]
+#[schemars(rename = "RPCLogPage")]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// Pass this as ``cursor`` to get the next (older) page, ``None`` if this is the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Returns one page of ``rpc_logs``, newest first
+///
+/// Pagination is keyed on ``(created_at, id)`` so new entries never shift the pages being read
+pub async fn query(pool: &PgPool, filter: &LogFilter) -> Result<LogPage, Error> {
+    let limit = filter.limit.unwrap_or(25).clamp(1, MAX_PAGE_SIZE);
+
+    let cursor = match &filter.cursor {
+        Some(cursor) => match cursor.parse::<Uuid>() {
+            Ok(id) => Some(id),
+            Err(_) => return Err(RPCFailure::invalid_field("cursor", "Invalid cursor").into()),
+        },
+        None => None,
+    };
+
+    // One extra row is fetched to know if there is another page
+    let mut rows = sqlx::query!(
+        "SELECT id, method, user_id, data, state, approved_by, batch_id, reverted_by, created_at FROM rpc_logs
+        WHERE ($1::text IS NULL OR user_id = $1)
+        AND ($2::text IS NULL OR method = $2)
+        AND ($3::text IS NULL OR data -> method ->> 'bot_id' = $3 OR data -> method -> 'bot_ids' ? $3)
+        AND ($4::text IS NULL OR (CASE WHEN $4 = 'error' THEN state NOT IN ('pending', 'success', 'scheduled', 'cancelled') ELSE state = $4 END))
+        AND ($5::timestamptz IS NULL OR created_at >= $5)
+        AND ($6::timestamptz IS NULL OR created_at < $6)
+        AND ($7::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM rpc_logs WHERE id = $7))
+        ORDER BY created_at DESC, id DESC LIMIT $8",
+        filter.user_id,
+        filter.method,
+        filter.bot_id,
+        filter.state,
+        filter.after,
+        filter.before,
+        cursor,
+        limit + 1
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|r| r.id.to_string())
+    } else {
+        None
+    };
+
+    Ok(LogPage {
+        entries: rows
+            .into_iter()
+            .map(|r| LogEntry {
+                id: r.id.to_string(),
+                method: r.method,
+                user_id: r.user_id,
+                data: r.data,
+                state: r.state,
+                approved_by: r.approved_by,
+                batch_id: r.batch_id.map(|b| b.to_string()),
+                reverted_by: r.reverted_by.map(|r| r.to_string()),
+                created_at: r.created_at,
+            })
+            .collect(),
+        next_cursor,
+    })
+}
+
+/// The longest date range that can be exported at once, in days
+pub const MAX_EXPORT_DAYS: i64 = 366;
+
+/// The whole UTC days an export covers, ``to`` included
+///
+/// Both the HTTP export and ``/audit export`` take their range in this form
+#[derive(Clone, Copy)]
+pub struct ExportRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl ExportRange {
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Result<Self, Error> {
+        if to < from {
+            return Err(RPCFailure::invalid_field(
+                "to",
+                "The end date must not be before the start date",
+            )
+            .into());
+        }
+
+        if (to - from).num_days() >= MAX_EXPORT_DAYS {
+            return Err(RPCFailure::invalid_field(
+                "to",
+                format!("At most {} days can be exported at once", MAX_EXPORT_DAYS),
+            )
+            .into());
+        }
+
+        Ok(ExportRange { from, to })
+    }
+
+    /// The range as timestamps, from the start of ``from`` to the start of the day after ``to`` (exclusive)
+    pub fn bounds(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start = |day: NaiveDate| Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN));
+
+        (start(self.from), start(self.to + Duration::days(1)))
+    }
+
+    pub fn filename(&self, format: ExportFormat) -> String {
+        format!(
+            "audit-{}-{}.{}",
+            self.from.format("%Y%m%d"),
+            self.to.format("%Y%m%d"),
+            format.extension()
+        )
+    }
+}
+
+#[derive(Deserialize, TS, Clone, Copy, PartialEq, Eq)]
+#[ts(export, export_to = ".generated/AuditExportFormat.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// What the file starts with, before any row
+    pub fn header(&self) -> &'static [u8] {
+        match self {
+            ExportFormat::Csv => CSV_HEADER.as_bytes(),
+            ExportFormat::Ndjson => b"",
+        }
+    }
+}
+
+/// One row of an audit export, from either ``rpc_logs`` or ``staff_general_logs``
+#[derive(Serialize)]
+pub struct AuditRow {
+    /// ``rpc`` or ``staff``
+    source: &'static str,
+    id: String,
+    created_at: DateTime<Utc>,
+    user_id: String,
+    /// The RPC method or the staff log action
+    action: String,
+    bot_id: Option<String>,
+    /// Only set for RPC calls
+    state: Option<String>,
+    data: serde_json::Value,
+}
+
+const CSV_HEADER: &str = "source,id,created_at,user_id,action,bot_id,state,data\n";
+
+/// Quotes a CSV field if needed
+fn csv_field(v: &str) -> String {
+    if v.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", v.replace('"', "\"\""))
+    } else {
+        v.to_string()
+    }
+}
+
+impl AuditRow {
+    /// Appends the row to ``out`` as one line of the file
+    pub fn write(&self, format: ExportFormat, out: &mut Vec<u8>) -> Result<(), Error> {
+        match format {
+            ExportFormat::Csv => {
+                let line = [
+                    self.source.to_string(),
+                    self.id.clone(),
+                    self.created_at.to_rfc3339(),
+                    self.user_id.clone(),
+                    self.action.clone(),
+                    self.bot_id.clone().unwrap_or_default(),
+                    self.state.clone().unwrap_or_default(),
+                    self.data.to_string(),
+                ]
+                .iter()
+                .map(|f| csv_field(f))
+                .collect::<Vec<_>>()
+                .join(",");
+
+                out.extend_from_slice(line.as_bytes());
+            }
+            ExportFormat::Ndjson => serde_json::to_writer(&mut *out, self)?,
+        }
+
+        out.push(b'\n');
+
+        Ok(())
+    }
+}
+
+/// Totals of an export, per staff member and action
+#[derive(Serialize, TS, Default)]
+#[ts(export, export_to = ".generated/AuditSummary.ts")]
+pub struct AuditSummary {
+    pub total: u64,
+    pub rpc_calls: u64,
+    pub staff_actions: u64,
+    /// Failed RPC calls, included in ``rpc_calls``
+    pub rpc_errors: u64,
+    pub by_user: Vec<UserTotals>,
+}
+
+#[derive(Serialize, TS)]
+#[ts(export, export_to = ".generated/AuditUserTotals.ts")]
+pub struct UserTotals {
+    pub user_id: String,
+    pub total: u64,
+    pub actions: BTreeMap<String, u64>,
+}
+
+impl AuditSummary {
+    /// Counts one row of the export
+    pub fn record(&mut self, row: &AuditRow) {
+        self.total += 1;
+
+        match row.state.as_deref() {
+            Some(state) => {
+                self.rpc_calls += 1;
+
+                if !matches!(state, "pending" | "success" | "scheduled" | "cancelled") {
+                    self.rpc_errors += 1;
+                }
+            }
+            None => self.staff_actions += 1,
+        }
+
+        let i = match self.by_user.iter().position(|t| t.user_id == row.user_id) {
+            Some(i) => i,
+            None => {
+                self.by_user.push(UserTotals {
+                    user_id: row.user_id.clone(),
+                    total: 0,
+                    actions: BTreeMap::new(),
+                });
+
+                self.by_user.len() - 1
+            }
+        };
+
+        let totals = &mut self.by_user[i];
+
+        totals.total += 1;
+        *totals.actions.entry(row.action.clone()).or_default() += 1;
+    }
+
+    /// Orders the totals busiest staff first, once every row is recorded
+    pub fn finish(&mut self) {
+        self.by_user.sort_by(|a, b| b.total.cmp(&a.total));
+    }
+}
+
+/// How many rows an export keeps fetched ahead of the reader
+const EXPORT_BUFFER: usize = 256;
+
+/// Streams the ``rpc_logs`` and then the ``staff_general_logs`` rows of ``range``, each oldest first
+///
+/// Rows are fetched in the background while they are read, so the export is never held in memory as a whole.
+/// A database error ends the stream with that error
+pub fn export(
+    pool: PgPool,
+    range: ExportRange,
+    user_id: Option<String>,
+) -> mpsc::Receiver<Result<AuditRow, Error>> {
+    let (tx, rx) = mpsc::channel(EXPORT_BUFFER);
+
+    tokio::spawn(async move {
+        if let Err(e) = send_rows(&pool, range, user_id.as_deref(), &tx).await {
+            // The reader may already be gone, then nobody is left to tell
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+
+    rx
+}
+
+/// Sends every row of an export to ``tx``, stopping early if the reader is gone
+async fn send_rows(
+    pool: &PgPool,
+    range: ExportRange,
+    user_id: Option<&str>,
+    tx: &mpsc::Sender<Result<AuditRow, Error>>,
+) -> Result<(), Error> {
+    let (from, to) = range.bounds();
+
+    let mut rpc_rows = sqlx::query!(
+        "SELECT id, method, user_id, data, state, created_at FROM rpc_logs WHERE created_at >= $1 AND created_at < $2 AND ($3::text IS NULL OR user_id = $3) ORDER BY created_at",
+        from,
+        to,
+        user_id
+    )
+    .fetch(pool);
+
+    while let Some(r) = rpc_rows.try_next().await? {
+        let bot_id = r
+            .data
+            .get(&r.method)
+            .and_then(|d| d.get("bot_id"))
+            .and_then(|b| b.as_str())
+            .map(|b| b.to_string());
+
+        let row = AuditRow {
+            source: "rpc",
+            id: r.id.to_string(),
+            created_at: r.created_at,
+            user_id: r.user_id,
+            action: r.method,
+            bot_id,
+            state: Some(r.state),
+            data: r.data,
+        };
+
+        if tx.send(Ok(row)).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    drop(rpc_rows);
+
+    let mut staff_rows = sqlx::query!(
+        "SELECT id::text AS \"id!\", user_id, action, data, created_at FROM staff_general_logs WHERE created_at >= $1 AND created_at < $2 AND ($3::text IS NULL OR user_id = $3) ORDER BY created_at",
+        from,
+        to,
+        user_id
+    )
+    .fetch(pool);
+
+    while let Some(r) = staff_rows.try_next().await? {
+        let bot_id = r
+            .data
+            .get("bot_id")
+            .and_then(|b| b.as_str())
+            .map(|b| b.to_string());
+
+        let row = AuditRow {
+            source: "staff",
+            id: r.id,
+            created_at: r.created_at,
+            user_id: r.user_id,
+            action: r.action,
+            bot_id,
+            state: None,
+            data: r.data,
+        };
+
+        if tx.send(Ok(row)).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts every row of an export without building the file
+pub async fn export_summary(
+    pool: &PgPool,
+    range: ExportRange,
+    user_id: Option<String>,
+) -> Result<AuditSummary, Error> {
+    let mut rows = export(pool.clone(), range, user_id);
+    let mut summary = AuditSummary::default();
+
+    while let Some(row) = rows.recv().await {
+        summary.record(&row?);
+    }
+
+    summary.finish();
+
+    Ok(summary)
+}
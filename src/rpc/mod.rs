@@ -1,4 +1,18 @@
+pub mod batch;
 pub mod core;
 
 pub mod command;
+pub mod effects;
+pub mod error;
+pub mod events;
+pub mod keychain;
+pub mod logs;
+pub mod openapi;
+pub mod protocol;
+pub mod quorum;
+pub mod ratelimit;
+pub mod revert;
+pub mod scheduled;
 pub mod server;
+pub mod signing;
+pub mod spec;
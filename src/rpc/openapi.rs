@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use schemars::gen::SchemaSettings;
+use schemars::visit::Visitor;
+use serde_json::{json, Map, Value};
+use strum::{IntoEnumIterator, VariantNames};
+
+use super::batch;
+use super::core::RPCMethod;
+use super::error::{RPCError, RPCErrorCode};
+use super::logs::{self, LogPage};
+use super::protocol;
+use super::server::{error_status, RPCBatchOutcome, RPCBatchRequest, RPCRequest, WebAction};
+use super::signing;
+use super::spec::FieldValidation;
+use crate::Error;
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Adds the JSON schema keywords equivalent to a field validation to a property
+fn add_validation(prop: &mut Value, validation: &FieldValidation) {
+    // Every rule except ``Items`` applies to each entry of a list
+    if prop.get("items").is_some() && !matches!(validation, FieldValidation::Items { .. }) {
+        return add_validation(&mut prop["items"], validation);
+    }
+
+    match validation {
+        FieldValidation::Snowflake => prop["pattern"] = json!("^[0-9]{17,20}$"),
+        FieldValidation::Uuid => prop["format"] = json!("uuid"),
+        FieldValidation::Length { min, max } => {
+            prop["minLength"] = json!(min);
+            prop["maxLength"] = json!(max);
+        }
+        // JSON schema counts characters and a character is 1 to 4 bytes, so these are the loosest
+        // character bounds that never reject a valid value, the description gives the real limit
+        FieldValidation::ByteLength { min, max } => {
+            prop["minLength"] = json!((min + 3) / 4);
+            prop["maxLength"] = json!(max);
+
+            let note = format!(
+                "Must be {} to {} bytes long once encoded as UTF-8",
+                min, max
+            );
+
+            prop["description"] = match prop["description"].as_str() {
+                Some(desc) => json!(format!("{}. {}", desc, note)),
+                None => json!(note),
+            };
+        }
+        FieldValidation::Range { min, max } => {
+            prop["minimum"] = json!(min);
+            prop["maximum"] = json!(max);
+        }
+        FieldValidation::Items { min, max } => {
+            prop["minItems"] = json!(min);
+            prop["maxItems"] = json!(max);
+        }
+    }
+}
+
+/// Adds the description, permissions and field validations from the spec of each method to the
+/// generated schema of ``RPCMethod``
+///
+/// ``RPCMethod`` is externally tagged, so every variant is an object with the method name as its only key
+fn annotate_methods(schema: &mut Value) -> Result<(), Error> {
+    let variants = match schema["oneOf"].as_array_mut() {
+        Some(variants) => variants,
+        None => return Err("The generated RPCMethod schema has no variants".into()),
+    };
+
+    for variant in variants {
+        let name = match variant["required"][0].as_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let spec = RPCMethod::from_str(&name)?.spec();
+
+        if let Some(fields) = variant["properties"][&name]["properties"].as_object_mut() {
+            for field in &spec.fields {
+                if let Some(prop) = fields.get_mut(&field.id) {
+                    prop["description"] = json!(field.label);
+
+                    for validation in &field.validation {
+                        add_validation(prop, validation);
+                    }
+                }
+            }
+        }
+
+        variant["title"] = json!(name);
+        variant["description"] = json!(spec.description);
+        variant["x-needed-perms"] = json!(spec.perms);
+        variant["x-min-protocol"] = json!(spec.min_protocol);
+    }
+
+    Ok(())
+}
+
+/// Generates the schemas of every type sent or returned by the API, keyed by their name in ``components``
+///
+/// Limits the types cannot express (such as the supported protocol versions) are added afterwards
+fn component_schemas() -> Result<Map<String, Value>, Error> {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    gen.subschema_for::<RPCRequest>();
+    gen.subschema_for::<RPCBatchRequest>();
+    gen.subschema_for::<RPCBatchOutcome>();
+    gen.subschema_for::<LogPage>();
+    gen.subschema_for::<WebAction>();
+    gen.subschema_for::<RPCError>();
+
+    let mut definitions = gen.take_definitions();
+
+    for visitor in gen.visitors_mut() {
+        for schema in definitions.values_mut() {
+            visitor.visit_schema(schema);
+        }
+    }
+
+    let mut schemas = match serde_json::to_value(definitions)? {
+        Value::Object(schemas) => schemas,
+        _ => return Err("Generated schemas are not an object".into()),
+    };
+
+    if let Some(method) = schemas.get_mut("RPCMethod") {
+        annotate_methods(method)?;
+    }
+
+    for name in ["RPCRequest", "RPCBatchRequest"] {
+        if let Some(protocol) = schemas
+            .get_mut(name)
+            .map(|s| &mut s["properties"]["protocol"])
+        {
+            protocol["minimum"] = json!(protocol::MIN_PROTOCOL);
+            protocol["maximum"] = json!(protocol::CURRENT_PROTOCOL);
+        }
+    }
+
+    if let Some(request) = schemas.get_mut("RPCBatchRequest") {
+        request["properties"]["methods"]["minItems"] = json!(1);
+        request["properties"]["methods"]["maxItems"] = json!(batch::MAX_BATCH);
+    }
+
+    Ok(schemas)
+}
+
+/// One response per HTTP status an ``RPCError`` can be returned with, listing the codes behind each
+fn error_responses() -> Map<String, Value> {
+    let mut codes: BTreeMap<u16, Vec<RPCErrorCode>> = BTreeMap::new();
+
+    for code in RPCErrorCode::iter() {
+        codes
+            .entry(error_status(code).as_u16())
+            .or_default()
+            .push(code);
+    }
+
+    let mut responses = Map::new();
+
+    for (status, codes) in codes {
+        let mut response = json!({
+            "description": format!(
+                "Error codes: {}",
+                codes
+                    .iter()
+                    .filter_map(|c| serde_json::to_value(c).ok())
+                    .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            "content": { "application/json": { "schema": schema_ref("RPCError") } },
+        });
+
+        if codes.contains(&RPCErrorCode::RateLimited) {
+            response["headers"] = json!({
+                "Retry-After": {
+                    "description": "Seconds to wait before retrying",
+                    "schema": { "type": "integer" },
+                }
+            });
+        }
+
+        responses.insert(status.to_string(), response);
+    }
+
+    responses
+}
+
+/// The signature headers of a signed request followed by ``params``, see ``signing::Signature``
+fn signed_params(params: Value) -> Value {
+    let header = |name: &str, description: &str| {
+        json!({
+            "name": name,
+            "in": "header",
+            "required": true,
+            "description": description,
+            "schema": { "type": "string" },
+        })
+    };
+
+    let mut all = vec![
+        header(
+            signing::KEY_HEADER,
+            "The key ID of the RPC identity, the identity file is `Bluejay$V1:<key ID>:<signing key>`",
+        ),
+        header(signing::TIMESTAMP_HEADER, "Unix timestamp the request was signed at"),
+        header(signing::NONCE_HEADER, "A random string (16 to 128 characters) unique to this request"),
+        header(
+            signing::SIGNATURE_HEADER,
+            "sha256=<hex HMAC-SHA256, keyed with the signing key, of \"<timestamp>\\n<nonce>\\n<HTTP method>\\n<path>\\n<body or query string>\">",
+        ),
+    ];
+
+    if let Value::Array(params) = params {
+        all.extend(params);
+    }
+
+    Value::Array(all)
+}
+
+/// How clients on ``protocol::LEGACY_AUTH_PROTOCOL`` authenticate, as they do not sign requests
+fn legacy_auth_note() -> String {
+    format!(
+        "Deprecated: protocol {} clients may leave the request unsigned and send `api_token` and `rpc_identity` (the whole identity file) in the body instead",
+        protocol::LEGACY_AUTH_PROTOCOL
+    )
+}
+
+/// Headers sent on every response that consumed a use of an RPC identity
+fn quota_headers() -> Value {
+    json!({
+        "X-RPC-Remaining-Uses": {
+            "description": "Uses left on the RPC identity",
+            "schema": { "type": "integer" },
+        },
+        "X-RPC-Expires-At": {
+            "description": "When the RPC identity expires",
+            "schema": { "type": "string", "format": "date-time" },
+        },
+        "X-RPC-Protocol": {
+            "description": "The newest protocol version spoken by the server",
+            "schema": { "type": "integer" },
+        },
+        "Deprecation": {
+            "description": "Set if the request used a deprecated protocol version",
+            "schema": { "type": "string" },
+        },
+    })
+}
+
+/// Adds the error responses to the success responses of a route
+fn with_errors(responses: Value) -> Value {
+    let mut responses = match responses {
+        Value::Object(responses) => responses,
+        _ => Map::new(),
+    };
+
+    responses.extend(error_responses());
+
+    Value::Object(responses)
+}
+
+/// Builds the OpenAPI 3 document served at ``/openapi.json``
+///
+/// Methods, permissions, error codes and their statuses are read from the same types the server uses,
+/// so adding a method or error code does not need the spec to be updated by hand
+pub fn spec() -> Result<Value, Error> {
+    let mut root = Map::new();
+
+    root.insert(
+        "/".to_string(),
+        json!({
+            "post": {
+                "summary": "Runs an RPC method",
+                "description": legacy_auth_note(),
+                "parameters": signed_params(json!([])),
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema_ref("RPCRequest") } },
+                },
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "The method returned content, or the diff of a dry run",
+                        "headers": quota_headers(),
+                        "content": {
+                            "text/plain": { "schema": { "type": "string" } },
+                            "application/json": { "schema": schema_ref("RPCDiff") },
+                        },
+                    },
+                    "202": {
+                        "description": "The method needs a second approval or was scheduled, the body is the action ID",
+                        "headers": quota_headers(),
+                        "content": { "text/plain": { "schema": { "type": "string", "format": "uuid" } } },
+                    },
+                    "204": {
+                        "description": "The method succeeded",
+                        "headers": quota_headers(),
+                    },
+                })),
+            }
+        }),
+    );
+
+    root.insert(
+        "/batch".to_string(),
+        json!({
+            "post": {
+                "summary": "Runs several RPC methods, charging a single use of the identity",
+                "description": format!(
+                    "A batch can contain at most {} methods. {}",
+                    batch::MAX_BATCH,
+                    legacy_auth_note()
+                ),
+                "parameters": signed_params(json!([])),
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema_ref("RPCBatchRequest") } },
+                },
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "The outcome of each method, in order",
+                        "headers": quota_headers(),
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": schema_ref("RPCBatchOutcome") }
+                            }
+                        },
+                    },
+                })),
+            }
+        }),
+    );
+
+    root.insert(
+        "/actions".to_string(),
+        json!({
+            "get": {
+                "summary": "Lists the methods a user may run, along with the fields needed to run them",
+                "parameters": [{
+                    "name": "user_id",
+                    "in": "query",
+                    "required": false,
+                    "description": "Only return methods this user has the permissions for",
+                    "schema": { "type": "string" },
+                }],
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "The available methods",
+                        "content": {
+                            "application/json": {
+                                "schema": { "type": "array", "items": schema_ref("RPCWebAction") }
+                            }
+                        },
+                    },
+                })),
+            }
+        }),
+    );
+
+    root.insert(
+        "/protocol".to_string(),
+        json!({
+            "get": {
+                "summary": "Lists the supported protocol versions and the methods of each",
+                "responses": {
+                    "200": {
+                        "description": "The supported protocol versions",
+                        "content": { "application/json": { "schema": { "type": "object" } } },
+                    },
+                },
+            }
+        }),
+    );
+
+    root.insert(
+        "/events".to_string(),
+        json!({
+            "get": {
+                "summary": "Streams moderation events as Server-Sent Events",
+                "description": "Signed like other GET requests (with an empty query string), but the signature is sent in the query string as EventSource cannot send headers. The stream is closed once the identity expires or is revoked",
+                "parameters": [
+                    { "name": "key", "in": "query", "required": true, "schema": { "type": "string" } },
+                    { "name": "timestamp", "in": "query", "required": true, "schema": { "type": "integer" } },
+                    { "name": "nonce", "in": "query", "required": true, "schema": { "type": "string" } },
+                    { "name": "signature", "in": "query", "required": true, "schema": { "type": "string" } },
+                ],
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "An event stream, the event name is the type of the event",
+                        "content": { "text/event-stream": { "schema": { "type": "string" } } },
+                    },
+                })),
+            }
+        }),
+    );
+
+    root.insert(
+        "/logs".to_string(),
+        json!({
+            "get": {
+                "summary": "Queries the RPC audit log, newest first. Head staff and above only",
+                "parameters": signed_params(json!([
+                    { "name": "user_id", "in": "query", "required": false, "schema": { "type": "string" } },
+                    { "name": "method", "in": "query", "required": false, "schema": { "type": "string", "enum": RPCMethod::VARIANTS } },
+                    { "name": "bot_id", "in": "query", "required": false, "schema": { "type": "string" } },
+                    { "name": "state", "in": "query", "required": false, "description": "pending, success, scheduled, cancelled or error", "schema": { "type": "string" } },
+                    { "name": "after", "in": "query", "required": false, "schema": { "type": "string", "format": "date-time" } },
+                    { "name": "before", "in": "query", "required": false, "schema": { "type": "string", "format": "date-time" } },
+                    { "name": "cursor", "in": "query", "required": false, "description": "The next_cursor of the previous page", "schema": { "type": "string" } },
+                    { "name": "limit", "in": "query", "required": false, "schema": { "type": "integer", "minimum": 1, "maximum": logs::MAX_PAGE_SIZE } },
+                ])),
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "One page of log entries",
+                        "content": { "application/json": { "schema": schema_ref("RPCLogPage") } },
+                    },
+                })),
+            }
+        }),
+    );
+
+    root.insert(
+        "/logs/export".to_string(),
+        json!({
+            "get": {
+                "summary": "Exports the RPC and staff audit logs for a date range. Head staff and above only",
+                "parameters": signed_params(json!([
+                    { "name": "from", "in": "query", "required": true, "description": "First day to export (UTC)", "schema": { "type": "string", "format": "date" } },
+                    { "name": "to", "in": "query", "required": true, "description": "Last day to export (UTC), inclusive", "schema": { "type": "string", "format": "date" } },
+                    { "name": "user_id", "in": "query", "required": false, "schema": { "type": "string" } },
+                    { "name": "format", "in": "query", "required": true, "schema": { "type": "string", "enum": ["csv", "ndjson"] } },
+                    { "name": "summary", "in": "query", "required": false, "description": "Only return the totals as JSON", "schema": { "type": "boolean", "default": false } },
+                ])),
+                "responses": with_errors(json!({
+                    "200": {
+                        "description": "The export file (streamed, so a failure part way cuts the file short), or its totals if summary is set",
+                        "content": {
+                            "text/csv": { "schema": { "type": "string" } },
+                            "application/x-ndjson": { "schema": { "type": "string" } },
+                            "application/json": { "schema": { "type": "object" } },
+                        },
+                    },
+                })),
+            }
+        }),
+    );
+
+    Ok(json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Arcadia RPC",
+            "version": env!("CARGO_PKG_VERSION"),
+            "x-rpc-protocol": protocol::CURRENT_PROTOCOL,
+        },
+        "paths": root,
+        "components": { "schemas": component_schemas()? },
+    }))
+}
@@ -0,0 +1,59 @@
+use std::convert::Infallible;
+
+use axum::{
+    http::HeaderValue,
+    response::{IntoResponseParts, ResponseParts},
+};
+
+/// The newest RPC protocol version spoken by this server
+pub const CURRENT_PROTOCOL: u8 = 6;
+
+/// The oldest RPC protocol version still accepted by this server
+///
+/// Versions older than ``CURRENT_PROTOCOL`` but at least this are deprecated, and still work for now
+/// so the frontend and the bot do not have to be deployed at the same instant
+///
+/// Protocol 5 is the oldest version the server ever spoke, nothing older is accepted
+pub const MIN_PROTOCOL: u8 = 5;
+
+/// The newest protocol version that sends the API token and RPC identity in the request body instead of
+/// signing requests (see ``signing``)
+///
+/// Unsigned requests cannot be protected from replays, so these are only accepted until protocol 5 is removed
+pub const LEGACY_AUTH_PROTOCOL: u8 = 5;
+
+/// Returns whether a protocol version is accepted at all
+pub fn is_supported(protocol: u8) -> bool {
+    (MIN_PROTOCOL..=CURRENT_PROTOCOL).contains(&protocol)
+}
+
+/// Returns whether a protocol version is accepted but will be removed in the future
+pub fn is_deprecated(protocol: u8) -> bool {
+    is_supported(protocol) && protocol < CURRENT_PROTOCOL
+}
+
+/// Response headers telling the client which protocol was used and whether it needs to update
+pub struct ProtocolHeaders(pub u8);
+
+impl IntoResponseParts for ProtocolHeaders {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut()
+            .insert("X-RPC-Protocol", HeaderValue::from(CURRENT_PROTOCOL));
+
+        if is_deprecated(self.0) {
+            res.headers_mut()
+                .insert("Deprecation", HeaderValue::from_static("true"));
+
+            if let Ok(warning) = HeaderValue::from_str(&format!(
+                "RPC protocol {} is deprecated and will stop working soon, please update to protocol {}",
+                self.0, CURRENT_PROTOCOL
+            )) {
+                res.headers_mut().insert("X-RPC-Protocol-Warning", warning);
+            }
+        }
+
+        Ok(res)
+    }
+}
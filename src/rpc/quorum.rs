@@ -0,0 +1,219 @@
+use poise::serenity_prelude::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+    CreateInteractionResponseFollowup, CreateMessage, EditInteractionResponse, Timestamp,
+};
+use sqlx::{types::Uuid, PgPool};
+
+use super::core::{RPCHandle, RPCMethod};
+use crate::{impls, impls::notifications::Notification, Error};
+
+/// How long a pending action waits for a second approval before it expires
+pub const QUORUM_TTL_MINUTES: i64 = 30;
+
+/// Saves a method call as a pending action and posts it to the staff server for approval
+pub async fn request_approval(state: &RPCHandle, method: &RPCMethod) -> Result<Uuid, Error> {
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(QUORUM_TTL_MINUTES);
+
+    let mut tx = state.pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "INSERT INTO rpc_pending_actions (method, data, user_id, expires_at) VALUES ($1, $2, $3, $4) RETURNING id",
+        method.to_string(),
+        serde_json::to_value(method)?,
+        &state.user_id,
+        expires_at
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let msg = CreateMessage::default()
+        .embed(
+            CreateEmbed::default()
+                .title("RPC Approval Needed")
+                .description(format!(
+                    "<@{}> wants to run `{}`. A second eligible user must approve this before it runs.",
+                    state.user_id, method
+                ))
+                .field(
+                    "Data",
+                    format!(
+                        "```json\n{}\n```",
+                        serde_json::to_string_pretty(method)?
+                    ),
+                    false,
+                )
+                .field(
+                    "Expires",
+                    format!("<t:{}:R>", expires_at.timestamp()),
+                    true,
+                )
+                .timestamp(Timestamp::now())
+                .color(0xFFA500),
+        )
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(format!("rpcq:approve:{}", rec.id))
+                .label("Approve")
+                .style(ButtonStyle::Success),
+            CreateButton::new(format!("rpcq:reject:{}", rec.id))
+                .label("Reject")
+                .style(ButtonStyle::Danger),
+        ])]);
+
+    Notification::new(crate::config::CONFIG.channels.rpc_approvals(), msg)
+        .dedup(format!("rpcq:{}", rec.id))
+        .enqueue(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(rec.id)
+}
+
+/// Handles a click on the Approve/Reject buttons of a pending action
+pub async fn handle_interaction(
+    pool: &PgPool,
+    cache_http: &impls::cache::CacheHttpImpl,
+    interaction: &ComponentInteraction,
+) -> Result<(), Error> {
+    let (approve, action_id) = match interaction
+        .data
+        .custom_id
+        .strip_prefix("rpcq:")
+        .and_then(|id| id.split_once(':'))
+    {
+        Some(("approve", id)) => (true, Uuid::parse_str(id)?),
+        Some(("reject", id)) => (false, Uuid::parse_str(id)?),
+        _ => return Err("Invalid quorum interaction".into()),
+    };
+
+    // Running the method can take longer than Discord waits for a response
+    interaction.defer(cache_http).await?;
+
+    let user_id = interaction.user.id.to_string();
+
+    let action = sqlx::query!(
+        "SELECT user_id, data FROM rpc_pending_actions WHERE id = $1 AND state = 'pending' AND expires_at > NOW()",
+        action_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let action = match action {
+        Some(action) => action,
+        None => {
+            return reply(
+                cache_http,
+                interaction,
+                "This action has already been decided or has expired",
+            )
+            .await
+        }
+    };
+
+    let method: RPCMethod = serde_json::from_value(action.data)?;
+
+    // The initiator may cancel their own action, but never approve it
+    if approve && action.user_id == user_id {
+        return reply(
+            cache_http,
+            interaction,
+            "You cannot approve your own action",
+        )
+        .await;
+    }
+
+    if action.user_id != user_id {
+        if let Err(e) = method.check_perms(pool, &user_id).await {
+            return reply(
+                cache_http,
+                interaction,
+                &format!("You are not eligible to decide on this action: {}", e),
+            )
+            .await;
+        }
+    }
+
+    // Claim the action first so it can only ever be decided (and run) once
+    let claimed = sqlx::query!(
+        "UPDATE rpc_pending_actions SET state = $1, decided_by = $2, decided_at = NOW() WHERE id = $3 AND state = 'pending' AND expires_at > NOW()",
+        if approve { "running" } else { "rejected" },
+        &user_id,
+        action_id
+    )
+    .execute(pool)
+    .await?;
+
+    if claimed.rows_affected() == 0 {
+        return reply(
+            cache_http,
+            interaction,
+            "This action has already been decided or has expired",
+        )
+        .await;
+    }
+
+    let content = if approve {
+        let res = method
+            .handle(RPCHandle {
+                pool: pool.clone(),
+                cache_http: cache_http.clone(),
+                user_id: action.user_id.clone(),
+                dry_run: false,
+                approved_by: Some(user_id.clone()),
+            })
+            .await;
+
+        sqlx::query!(
+            "UPDATE rpc_pending_actions SET state = $1 WHERE id = $2",
+            if res.is_ok() { "approved" } else { "failed" },
+            action_id
+        )
+        .execute(pool)
+        .await?;
+
+        match res {
+            Ok(_) => format!(
+                "`{}` by <@{}> was approved by <@{}> and has been run",
+                method, action.user_id, user_id
+            ),
+            Err(e) => format!(
+                "`{}` by <@{}> was approved by <@{}> but failed: **{}**",
+                method, action.user_id, user_id, e
+            ),
+        }
+    } else {
+        format!(
+            "`{}` by <@{}> was rejected by <@{}>",
+            method, action.user_id, user_id
+        )
+    };
+
+    interaction
+        .edit_response(
+            cache_http,
+            EditInteractionResponse::new()
+                .content(content)
+                .components(Vec::new()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Replies to the user who clicked a button without touching the pending action
+async fn reply(
+    cache_http: &impls::cache::CacheHttpImpl,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    interaction
+        .create_followup(
+            cache_http,
+            CreateInteractionResponseFollowup::default()
+                .content(content)
+                .ephemeral(true),
+        )
+        .await?;
+
+    Ok(())
+}
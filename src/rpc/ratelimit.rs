@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::CreateMessage;
+
+use super::core::{RPCHandle, RPCMethod, RPCPerms};
+use super::error::RPCFailure;
+use crate::config::{RateLimit, CONFIG};
+use crate::{impls::notifications::Notification, Error};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let regained = now.duration_since(self.updated).as_secs_f64() / limit.refill_secs as f64;
+
+        self.tokens = (self.tokens + regained).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// How long until ``count`` more calls can be made
+    fn wait(&self, limit: RateLimit, count: u32) -> Duration {
+        Duration::from_secs_f64((count as f64 - self.tokens).max(0.0) * limit.refill_secs as f64)
+    }
+}
+
+/// Token buckets, keyed by ``<tier or method>:<user id>``
+///
+/// Buckets and strikes are kept in memory, so every instance of the RPC server rate limits on its own.
+/// Only the lock that follows repeated strikes is stored in ``rpc_locks`` and shared by all instances
+static BUCKETS: Lazy<Mutex<HashMap<String, Bucket>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// When each user was last rate limited, used to decide when to lock RPC for them
+static STRIKES: Lazy<Mutex<HashMap<String, Vec<Instant>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn tier_limit(perms: RPCPerms) -> (&'static str, RateLimit) {
+    let limits = &CONFIG.rpc_ratelimits;
+
+    match perms {
+        RPCPerms::Owner => ("owner", limits.owner),
+        RPCPerms::Head => ("head", limits.head),
+        RPCPerms::Admin => ("admin", limits.admin),
+        RPCPerms::Staff => ("staff", limits.staff),
+    }
+}
+
+/// The buckets the methods fall under, with how many tokens each of them costs
+///
+/// Every method takes its own token, so a batch costs as much as making its calls one by one
+fn charges(user_id: &str, methods: &[RPCMethod]) -> HashMap<String, (RateLimit, u32)> {
+    let mut charges = HashMap::new();
+
+    for method in methods {
+        let (tier, limit) = tier_limit(method.needs_perms());
+        charges
+            .entry(format!("{}:{}", tier, user_id))
+            .or_insert((limit, 0))
+            .1 += 1;
+
+        if let Some(limit) = CONFIG.rpc_ratelimits.methods.get(&method.to_string()) {
+            charges
+                .entry(format!("{}:{}", method, user_id))
+                .or_insert((*limit, 0))
+                .1 += 1;
+        }
+    }
+
+    charges
+}
+
+/// Takes the charged tokens from every bucket, or none at all if any bucket does not have enough
+///
+/// Returns how long to wait before retrying if the call is rate limited
+fn take(
+    buckets: &mut HashMap<String, Bucket>,
+    charges: &HashMap<String, (RateLimit, u32)>,
+    now: Instant,
+) -> Option<Duration> {
+    let mut wait = Duration::ZERO;
+
+    for (key, (limit, count)) in charges {
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+            tokens: limit.burst as f64,
+            updated: now,
+        });
+
+        bucket.refill(*limit, now);
+
+        if bucket.tokens < *count as f64 {
+            wait = wait.max(bucket.wait(*limit, *count));
+        }
+    }
+
+    if !wait.is_zero() {
+        return Some(wait);
+    }
+
+    for (key, (_, count)) in charges {
+        if let Some(bucket) = buckets.get_mut(key) {
+            bucket.tokens -= *count as f64;
+        }
+    }
+
+    None
+}
+
+/// Records a rate limited call, returning whether the user has now hit the lock threshold
+fn strike(user_id: &str) -> Result<bool, Error> {
+    let limits = &CONFIG.rpc_ratelimits;
+    let window = Duration::from_secs(limits.strike_window_secs);
+
+    let mut strikes = STRIKES.lock().map_err(|_| "Rate limiter is poisoned")?;
+    let user_strikes = strikes.entry(user_id.to_string()).or_default();
+
+    let now = Instant::now();
+    user_strikes.retain(|s| now.duration_since(*s) < window);
+    user_strikes.push(now);
+
+    if user_strikes.len() as u32 >= limits.lock_after_strikes {
+        user_strikes.clear();
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Locks RPC (and only RPC) for a user and lets the owners know
+async fn lock(state: &RPCHandle) -> Result<(), Error> {
+    let locked_until =
+        chrono::Utc::now() + chrono::Duration::minutes(CONFIG.rpc_ratelimits.lock_minutes);
+
+    sqlx::query!(
+        "INSERT INTO rpc_locks (user_id, locked_until, reason) VALUES ($1, $2, $3) ON CONFLICT (user_id) DO UPDATE SET locked_until = EXCLUDED.locked_until, reason = EXCLUDED.reason",
+        &state.user_id,
+        locked_until,
+        "Repeatedly exceeded the RPC rate limit"
+    )
+    .execute(&state.pool)
+    .await?;
+
+    warn!("Locked RPC for {} until {}", state.user_id, locked_until);
+
+    let owners = CONFIG
+        .owners
+        .iter()
+        .map(|o| format!("<@{}>", o))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Notification::new(
+        CONFIG.channels.rpc_approvals(),
+        CreateMessage::default().content(format!(
+            "{} RPC has been locked for <@{}> until <t:{}:f> as they repeatedly exceeded the rate limit",
+            owners,
+            state.user_id,
+            locked_until.timestamp()
+        )),
+    )
+    .dedup(format!(
+        "rpclock:{}:{}",
+        state.user_id,
+        locked_until.timestamp()
+    ))
+    .enqueue(&state.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Checks that a user may make a call using the given methods, consuming one token per method from each bucket
+///
+/// Users who keep getting rate limited have RPC locked for them, their site session is never touched
+pub async fn check(state: &RPCHandle, methods: &[RPCMethod]) -> Result<(), Error> {
+    let locked = sqlx::query!(
+        "SELECT locked_until FROM rpc_locks WHERE user_id = $1 AND locked_until > NOW()",
+        &state.user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    if let Some(locked) = locked {
+        let wait = (locked.locked_until - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default();
+
+        return Err(RPCFailure::rate_limited_for(
+            "RPC has been locked for your account as you repeatedly exceeded the rate limit. Contact an owner if this is a mistake",
+            wait,
+        )
+        .into());
+    }
+
+    let charges = charges(&state.user_id, methods);
+
+    let wait = {
+        let mut buckets = BUCKETS.lock().map_err(|_| "Rate limiter is poisoned")?;
+
+        match take(&mut buckets, &charges, Instant::now()) {
+            Some(wait) => wait,
+            None => return Ok(()),
+        }
+    };
+
+    if strike(&state.user_id)? {
+        lock(state).await?;
+    }
+
+    Err(RPCFailure::rate_limited_for(
+        format!(
+            "Rate limit exceeded. Try again in {} seconds",
+            wait.as_secs().max(1)
+        ),
+        wait,
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        burst: 3,
+        refill_secs: 10,
+    };
+
+    fn charge(key: &str, count: u32) -> HashMap<String, (RateLimit, u32)> {
+        HashMap::from([(key.to_string(), (LIMIT, count))])
+    }
+
+    #[test]
+    fn take_charges_every_method() {
+        let mut buckets = HashMap::new();
+        let now = Instant::now();
+
+        assert!(take(&mut buckets, &charge("staff:1", 2), now).is_none());
+        assert_eq!(buckets["staff:1"].tokens, 1.0);
+
+        // Two more calls need a second token which regains in 10 seconds
+        assert_eq!(
+            take(&mut buckets, &charge("staff:1", 2), now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(buckets["staff:1"].tokens, 1.0);
+    }
+
+    #[test]
+    fn take_is_all_or_nothing() {
+        let mut buckets = HashMap::new();
+        let now = Instant::now();
+
+        assert!(take(&mut buckets, &charge("Method:1", 3), now).is_none());
+
+        let mut charges = charge("staff:1", 1);
+        charges.insert("Method:1".to_string(), (LIMIT, 1));
+
+        assert!(take(&mut buckets, &charges, now).is_some());
+        assert_eq!(buckets["staff:1"].tokens, 3.0);
+    }
+
+    #[test]
+    fn take_refills_over_time() {
+        let mut buckets = HashMap::new();
+        let now = Instant::now();
+
+        assert!(take(&mut buckets, &charge("staff:1", 3), now).is_none());
+        assert!(take(&mut buckets, &charge("staff:1", 1), now).is_some());
+        assert!(take(
+            &mut buckets,
+            &charge("staff:1", 1),
+            now + Duration::from_secs(10)
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,360 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, Postgres, Transaction};
+
+use super::effects::RPCEffects;
+use crate::Error;
+
+/// The state a reversible method overwrote, saved in ``rpc_logs.before_image`` when it runs
+///
+/// Timestamps, intervals and UUIDs are kept as text so the image round-trips through JSON unchanged.
+/// The rows are locked when captured, so nothing else can change them until the method commits
+#[derive(Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum BeforeImage {
+    BotPremium {
+        bot_id: String,
+        premium: bool,
+        start_premium_period: Option<String>,
+        premium_period_length: Option<String>,
+    },
+    BotVoteBan {
+        bot_id: String,
+        vote_banned: bool,
+    },
+    BotType {
+        bot_id: String,
+        r#type: String,
+    },
+    BotOwner {
+        bot_id: String,
+        owner: Option<String>,
+        team_owner: Option<String>,
+    },
+    TeamName {
+        team_id: String,
+        name: String,
+    },
+    /// Saved by ``BotRequeue``, reverting it also removes the resubmission it recorded
+    BotRequeue {
+        bot_id: String,
+        r#type: String,
+        resubmission_id: String,
+    },
+    /// Saved by the bulk methods, one entry per bot
+    BotClaims {
+        bots: Vec<BotClaim>,
+    },
+}
+
+/// The review state of one bot in ``BeforeImage::BotClaims``
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct BotClaim {
+    pub bot_id: String,
+    pub r#type: String,
+    pub claimed_by: Option<String>,
+}
+
+impl BeforeImage {
+    /// Captures the premium state of a bot
+    pub async fn bot_premium(
+        tx: &mut Transaction<'_, Postgres>,
+        bot_id: &str,
+    ) -> Result<Self, Error> {
+        let rec = sqlx::query!(
+            "SELECT premium, start_premium_period::text, premium_period_length::text FROM bots WHERE bot_id = $1 FOR UPDATE",
+            bot_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(Self::BotPremium {
+            bot_id: bot_id.to_string(),
+            premium: rec.premium,
+            start_premium_period: rec.start_premium_period,
+            premium_period_length: rec.premium_period_length,
+        })
+    }
+
+    /// Captures the vote ban state of a bot
+    pub async fn bot_vote_ban(
+        tx: &mut Transaction<'_, Postgres>,
+        bot_id: &str,
+    ) -> Result<Self, Error> {
+        let rec = sqlx::query!(
+            "SELECT vote_banned FROM bots WHERE bot_id = $1 FOR UPDATE",
+            bot_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(Self::BotVoteBan {
+            bot_id: bot_id.to_string(),
+            vote_banned: rec.vote_banned,
+        })
+    }
+
+    /// Captures the type (approved, certified etc.) of a bot
+    pub async fn bot_type(tx: &mut Transaction<'_, Postgres>, bot_id: &str) -> Result<Self, Error> {
+        let rec = sqlx::query!("SELECT type FROM bots WHERE bot_id = $1 FOR UPDATE", bot_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        Ok(Self::BotType {
+            bot_id: bot_id.to_string(),
+            r#type: rec.r#type,
+        })
+    }
+
+    /// Captures the user and team owner of a bot
+    pub async fn bot_owner(
+        tx: &mut Transaction<'_, Postgres>,
+        bot_id: &str,
+    ) -> Result<Self, Error> {
+        let rec = sqlx::query!(
+            "SELECT owner, team_owner::text FROM bots WHERE bot_id = $1 FOR UPDATE",
+            bot_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(Self::BotOwner {
+            bot_id: bot_id.to_string(),
+            owner: rec.owner,
+            team_owner: rec.team_owner,
+        })
+    }
+
+    /// Captures the name of a team
+    pub async fn team_name(
+        tx: &mut Transaction<'_, Postgres>,
+        team_id: Uuid,
+    ) -> Result<Self, Error> {
+        let rec = sqlx::query!("SELECT name FROM teams WHERE id = $1 FOR UPDATE", team_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        Ok(Self::TeamName {
+            team_id: team_id.to_string(),
+            name: rec.name,
+        })
+    }
+
+    /// Captures the type of a requeued bot, along with the resubmission recorded for it
+    pub async fn bot_requeue(
+        tx: &mut Transaction<'_, Postgres>,
+        bot_id: &str,
+        resubmission_id: &str,
+    ) -> Result<Self, Error> {
+        let rec = sqlx::query!("SELECT type FROM bots WHERE bot_id = $1 FOR UPDATE", bot_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        Ok(Self::BotRequeue {
+            bot_id: bot_id.to_string(),
+            r#type: rec.r#type,
+            resubmission_id: resubmission_id.to_string(),
+        })
+    }
+
+    /// Captures the type and claim of many bots
+    pub async fn bot_claims(
+        tx: &mut Transaction<'_, Postgres>,
+        bot_ids: &[String],
+    ) -> Result<Self, Error> {
+        let mut bots = Vec::new();
+
+        for bot_id in bot_ids {
+            let rec = sqlx::query!(
+                "SELECT type, claimed_by FROM bots WHERE bot_id = $1 FOR UPDATE",
+                bot_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            bots.push(BotClaim {
+                bot_id: bot_id.to_string(),
+                r#type: rec.r#type,
+                claimed_by: rec.claimed_by,
+            });
+        }
+
+        Ok(Self::BotClaims { bots })
+    }
+
+    /// Captures the current state of whatever this image was taken of
+    ///
+    /// Saved as the after-image of a method, so a revert can tell if something else changed it since
+    pub async fn current(&self, tx: &mut Transaction<'_, Postgres>) -> Result<Self, Error> {
+        match self {
+            Self::BotPremium { bot_id, .. } => Self::bot_premium(tx, bot_id).await,
+            Self::BotVoteBan { bot_id, .. } => Self::bot_vote_ban(tx, bot_id).await,
+            Self::BotType { bot_id, .. } => Self::bot_type(tx, bot_id).await,
+            Self::BotOwner { bot_id, .. } => Self::bot_owner(tx, bot_id).await,
+            Self::TeamName { team_id, .. } => Self::team_name(tx, team_id.parse()?).await,
+            Self::BotRequeue {
+                bot_id,
+                resubmission_id,
+                ..
+            } => Self::bot_requeue(tx, bot_id, resubmission_id).await,
+            Self::BotClaims { bots } => {
+                let bot_ids = bots.iter().map(|b| b.bot_id.clone()).collect::<Vec<_>>();
+                Self::bot_claims(tx, &bot_ids).await
+            }
+        }
+    }
+
+    /// Returns a short description of what restoring this image changes, for the mod logs
+    pub fn describe(&self) -> String {
+        match self {
+            Self::BotPremium {
+                bot_id, premium, ..
+            } => format!("Premium of <@{}> restored to `{}`", bot_id, premium),
+            Self::BotVoteBan {
+                bot_id,
+                vote_banned,
+            } => format!("Vote ban of <@{}> restored to `{}`", bot_id, vote_banned),
+            Self::BotType { bot_id, r#type } => {
+                format!("Type of <@{}> restored to `{}`", bot_id, r#type)
+            }
+            Self::BotOwner {
+                bot_id,
+                owner,
+                team_owner,
+            } => match (owner, team_owner) {
+                (_, Some(team)) => format!("Owner of <@{}> restored to team {}", bot_id, team),
+                (Some(owner), None) => format!("Owner of <@{}> restored to <@{}>", bot_id, owner),
+                (None, None) => format!("Owner of <@{}> cleared", bot_id),
+            },
+            Self::TeamName { team_id, name } => {
+                format!("Name of team {} restored to `{}`", team_id, name)
+            }
+            Self::BotRequeue { bot_id, r#type, .. } => format!(
+                "Type of <@{}> restored to `{}` and its resubmission removed",
+                bot_id, r#type
+            ),
+            Self::BotClaims { bots } => {
+                format!("Type and claim of {} bots restored", bots.len())
+            }
+        }
+    }
+
+    /// Writes the saved state back
+    pub async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        effects: &mut RPCEffects,
+    ) -> Result<(), Error> {
+        match self {
+            Self::BotPremium {
+                bot_id,
+                premium,
+                start_premium_period,
+                premium_period_length,
+            } => {
+                let res = sqlx::query!(
+                    "UPDATE bots SET premium = $2, start_premium_period = $3::text::timestamptz, premium_period_length = $4::text::interval WHERE bot_id = $1",
+                    bot_id,
+                    premium,
+                    start_premium_period.as_deref(),
+                    premium_period_length.as_deref()
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bots", res);
+            }
+            Self::BotVoteBan {
+                bot_id,
+                vote_banned,
+            } => {
+                let res = sqlx::query!(
+                    "UPDATE bots SET vote_banned = $2 WHERE bot_id = $1",
+                    bot_id,
+                    vote_banned
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bots", res);
+            }
+            Self::BotType { bot_id, r#type } => {
+                let res = sqlx::query!(
+                    "UPDATE bots SET type = $2 WHERE bot_id = $1",
+                    bot_id,
+                    r#type
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bots", res);
+            }
+            Self::BotOwner {
+                bot_id,
+                owner,
+                team_owner,
+            } => {
+                let res = sqlx::query!(
+                    "UPDATE bots SET owner = $2, team_owner = $3::text::uuid WHERE bot_id = $1",
+                    bot_id,
+                    owner.as_deref(),
+                    team_owner.as_deref()
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bots", res);
+            }
+            Self::TeamName { team_id, name } => {
+                let res = sqlx::query!(
+                    "UPDATE teams SET name = $2 WHERE id = $1",
+                    team_id.parse::<Uuid>()?,
+                    name
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("teams", res);
+            }
+            Self::BotRequeue {
+                bot_id,
+                r#type,
+                resubmission_id,
+            } => {
+                let res = sqlx::query!(
+                    "UPDATE bots SET type = $2 WHERE bot_id = $1",
+                    bot_id,
+                    r#type
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bots", res);
+
+                let res = sqlx::query!(
+                    "DELETE FROM bot_resubmissions WHERE id = $1",
+                    resubmission_id.parse::<Uuid>()?
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                effects.touched("bot_resubmissions", res);
+            }
+            Self::BotClaims { bots } => {
+                for bot in bots {
+                    let res = sqlx::query!(
+                        "UPDATE bots SET type = $2, claimed_by = $3 WHERE bot_id = $1",
+                        &bot.bot_id,
+                        &bot.r#type,
+                        bot.claimed_by.as_deref()
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    effects.touched("bots", res);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
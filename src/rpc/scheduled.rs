@@ -0,0 +1,182 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Uuid, PgPool};
+
+use super::core::{self, set_log_state, RPCHandle, RPCMethod, RPCSuccess};
+use super::error::RPCFailure;
+use crate::{impls, Error};
+
+/// Calls still marked as running after this long are assumed to have been interrupted and are picked up again
+pub const STALE_CLAIM_MINUTES: i32 = 15;
+
+/// An RPC call waiting in the ``rpc_scheduled`` table
+pub struct ScheduledAction {
+    pub id: Uuid,
+    pub user_id: String,
+    pub method: String,
+    pub execute_at: DateTime<Utc>,
+}
+
+/// Saves a method call to be run later as the user of ``state``
+///
+/// The call goes through the same checks as running it now (taking its rate limit token) and is logged in
+/// ``rpc_logs`` as ``scheduled``. Permissions are checked again when the call runs, under the same log entry
+pub async fn schedule(
+    state: &RPCHandle,
+    method: &RPCMethod,
+    execute_at: DateTime<Utc>,
+) -> Result<Uuid, Error> {
+    if execute_at <= Utc::now() {
+        return Err(RPCFailure::invalid_argument("execute_at must be in the future").into());
+    }
+
+    check_no_quorum(method)?;
+
+    core::precheck(state, std::slice::from_ref(method)).await?;
+
+    let log_id = method.log(state, None).await?;
+
+    set_log_state(&state.pool, log_id, "scheduled").await?;
+
+    let rec = sqlx::query!(
+        "INSERT INTO rpc_scheduled (user_id, method, data, execute_at, log_id) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        &state.user_id,
+        method.to_string(),
+        serde_json::to_value(method)?,
+        execute_at,
+        log_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(rec.id)
+}
+
+/// Returns all scheduled calls that have not run yet, soonest first
+pub async fn pending(pool: &PgPool) -> Result<Vec<ScheduledAction>, Error> {
+    let recs = sqlx::query!(
+        "SELECT id, user_id, method, execute_at FROM rpc_scheduled WHERE state = 'pending' ORDER BY execute_at"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs
+        .into_iter()
+        .map(|r| ScheduledAction {
+            id: r.id,
+            user_id: r.user_id,
+            method: r.method,
+            execute_at: r.execute_at,
+        })
+        .collect())
+}
+
+/// Cancels a scheduled call and marks its log entry ``cancelled``, returning whether it was still pending
+///
+/// Only the user who scheduled the call (or an owner) may cancel it
+pub async fn cancel(pool: &PgPool, id: Uuid, user_id: &str, is_owner: bool) -> Result<bool, Error> {
+    let res = sqlx::query!(
+        "WITH cancelled AS (UPDATE rpc_scheduled SET state = 'cancelled', cancelled_by = $2 WHERE id = $1 AND state = 'pending' AND (user_id = $2 OR $3) RETURNING log_id) UPDATE rpc_logs SET state = 'cancelled' WHERE id IN (SELECT log_id FROM cancelled)",
+        id,
+        user_id,
+        is_owner
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Scheduled calls run unattended, so methods needing a second approval cannot be scheduled
+fn check_no_quorum(method: &RPCMethod) -> Result<(), Error> {
+    if method.needs_quorum() {
+        return Err(RPCFailure::invalid_argument(format!(
+            "`{}` needs a second approval and cannot be scheduled",
+            method
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Runs a claimed call under the log entry made when it was scheduled
+///
+/// The rate limit token was taken when the call was scheduled, so only access is checked again here
+async fn run_claimed(
+    method: &RPCMethod,
+    state: &RPCHandle,
+    log_id: Uuid,
+) -> Result<RPCSuccess, Error> {
+    let checked = match check_no_quorum(method) {
+        Ok(()) => core::check_access(state, std::slice::from_ref(method)).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = checked {
+        set_log_state(&state.pool, log_id, &e.to_string()).await?;
+
+        return Err(e);
+    }
+
+    method.run(state, log_id).await
+}
+
+/// Runs all scheduled calls that are due as their original invoker
+pub async fn run_due(pool: &PgPool, cache_http: &impls::cache::CacheHttpImpl) -> Result<(), Error> {
+    // A run commits its log state in the same transaction as the method, so a stale claim whose log
+    // is no longer ``scheduled`` was applied (or failed) and only needs its outcome copied over
+    sqlx::query!(
+        "UPDATE rpc_scheduled s SET state = CASE WHEN l.state = 'success' THEN 'done' ELSE 'failed' END, last_error = CASE WHEN l.state = 'success' THEN NULL ELSE l.state END, ran_at = NOW() FROM rpc_logs l WHERE l.id = s.log_id AND s.state = 'running' AND s.claimed_at < NOW() - make_interval(mins => $1) AND l.state != 'scheduled'",
+        STALE_CLAIM_MINUTES
+    )
+    .execute(pool)
+    .await?;
+
+    // Claim due calls first so a slow run can never be picked up twice.
+    // Claims left behind by a run that never finished (such as a restart) are taken over once stale,
+    // but only while their log shows the method was never applied
+    let due = sqlx::query!(
+        "UPDATE rpc_scheduled SET state = 'running', claimed_at = NOW() WHERE id IN (SELECT s.id FROM rpc_scheduled s JOIN rpc_logs l ON l.id = s.log_id WHERE s.execute_at <= NOW() AND l.state = 'scheduled' AND (s.state = 'pending' OR (s.state = 'running' AND s.claimed_at < NOW() - make_interval(mins => $1))) ORDER BY s.execute_at LIMIT 10 FOR UPDATE OF s SKIP LOCKED) RETURNING id, user_id, data, log_id",
+        STALE_CLAIM_MINUTES
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in due {
+        let state = RPCHandle {
+            pool: pool.clone(),
+            cache_http: cache_http.clone(),
+            user_id: row.user_id.clone(),
+            dry_run: false,
+            approved_by: None,
+        };
+
+        let res = match serde_json::from_value::<RPCMethod>(row.data) {
+            Ok(method) => run_claimed(&method, &state, row.log_id).await,
+            Err(e) => {
+                set_log_state(pool, row.log_id, &e.to_string()).await?;
+
+                Err(e.into())
+            }
+        };
+
+        let (state, last_error) = match res {
+            Ok(_) => ("done", None),
+            Err(e) => {
+                log::warn!("Scheduled RPC call {} failed: {}", row.id, e);
+                ("failed", Some(e.to_string()))
+            }
+        };
+
+        sqlx::query!(
+            "UPDATE rpc_scheduled SET state = $1, last_error = $2, ran_at = NOW() WHERE id = $3",
+            state,
+            last_error,
+            row.id
+        )
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,55 +1,91 @@
-use std::{str::FromStr, time::Duration};
+use std::convert::Infallible;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::impls;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    body::{Bytes, StreamBody},
+    extract::{DefaultBodyLimit, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, IntoResponseParts, Response, ResponseParts,
+    },
     routing::{get, post},
     Json, Router,
 };
-use log::info;
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::{Stream, StreamExt};
+use log::{error, info, warn};
 use sqlx::PgPool;
 use strum::VariantNames;
-use tower_http::cors::{Any, CorsLayer};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 use super::core::{RPCHandle, RPCMethod, RPCPerms, RPCSuccess};
-use serde::{Deserialize, Serialize};
+use super::effects::RPCDiff;
+use super::error::{RPCError, RPCErrorCode, RPCFailure};
+use super::events;
+use super::keychain::{KeychainData, KeychainQuota};
+use super::logs::{self, ExportFormat, ExportRange, LogFilter, LogPage};
+use super::protocol::{self, ProtocolHeaders};
+use super::signing::{self, Signature};
+use super::spec::WebField;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use ts_rs::TS;
-use once_cell::sync::Lazy;
-use moka::future::Cache;
 
-pub static RPC_KEYCHAIN: Lazy<Cache<String, KeychainData>> = Lazy::new(|| {
-    info!("RPCKeychain initialized");
-
-    Cache::builder()
-        // Time to live (TTL): 15 minutes
-        .time_to_live(Duration::from_secs(5 * 60))        // Create the cache.
-        .build()
-});
-
-#[derive(Clone)]
-pub struct KeychainData {
-    pub user_id: String,
-    pub allowed_methods: Vec<String>,
-    pub max_uses: u8,
-    pub used: u8,
-    pub reason: String,
-}
-
-#[derive(Deserialize, TS)]
+#[derive(Deserialize, TS, JsonSchema)]
 #[ts(export, export_to = ".generated/RPCRequest.ts")]
 pub struct RPCRequest {
     pub user_id: String,
     pub method: RPCMethod,
-    pub api_token: String,
-    pub rpc_identity: String,
     pub protocol: u8,
+    /// Run all validation and permission checks, returning what would change without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Run the method at this time (as the same user) instead of now
+    #[serde(default)]
+    #[ts(type = "string | null")]
+    pub execute_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCBatchRequest.ts")]
+pub struct RPCBatchRequest {
+    pub user_id: String,
+    pub methods: Vec<RPCMethod>,
+    pub protocol: u8,
+    /// Run all methods in one transaction, if one fails none are applied
+    #[serde(default)]
+    pub atomic: bool,
+    /// Run all validation and permission checks, returning what would change without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// The outcome of one method of a batch
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCBatchOutcome.ts")]
+pub struct RPCBatchOutcome {
+    pub method: String,
+    pub result: RPCBatchResult,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+#[ts(export, export_to = ".generated/RPCBatchResult.ts")]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RPCBatchResult {
+    Success { content: Option<String> },
+    DryRun { diff: RPCDiff },
+    PendingApproval { action_id: String },
+    Error { error: RPCError },
 }
 
 pub enum RPCResponse {
     Err(String),
+    Method(crate::Error),
     InvalidProtocol,
     InvalidIdentity,
     UsageQuoteExceeded,
@@ -61,29 +97,96 @@ pub enum RPCResponse {
 pub enum Success {
     Content(String),
     NoContent,
+    DryRun(RPCDiff),
+    PendingApproval(String),
+    Scheduled(String),
 }
 
-impl IntoResponse for RPCResponse {
-    fn into_response(self) -> Response {
-        match self {
-            Self::Err(err) => (StatusCode::BAD_REQUEST, err).into_response(),
-            Self::InvalidProtocol => (
-                StatusCode::PRECONDITION_FAILED,
-                "Out of date client. Please use the bot until this is fixed",
-            )
-                .into_response(),
-            Self::UserNotFound => {
-                (StatusCode::NOT_FOUND, "This user could not be found. Try logging out and logging in again?").into_response()
+impl RPCResponse {
+    fn error(&self) -> RPCError {
+        let (code, message) = match self {
+            Self::Err(err) => (RPCErrorCode::Internal, err.as_str()),
+            Self::Method(err) => return err.into(),
+            Self::InvalidProtocol => {
+                return RPCError {
+                    code: RPCErrorCode::InvalidProtocol,
+                    message: "Out of date client. Please use the bot until this is fixed"
+                        .to_string(),
+                    retryable: false,
+                    details: Some(serde_json::json!({
+                        "min_protocol": protocol::MIN_PROTOCOL,
+                        "current_protocol": protocol::CURRENT_PROTOCOL,
+                    })),
+                }
             }
+            Self::UserNotFound => (
+                RPCErrorCode::UserNotFound,
+                "This user could not be found. Try logging out and logging in again?",
+            ),
             Self::InvalidIdentity => (
-                StatusCode::UNAUTHORIZED,
+                RPCErrorCode::InvalidIdentity,
                 "Invalid RPC identity. Generate a new one?",
-            )
-                .into_response(),
-            Self::UsageQuoteExceeded => (StatusCode::METHOD_NOT_ALLOWED, "Usage quotas exceeded for this RPC identity, generate another one?").into_response(),
-            Self::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed for this RPC identity").into_response(),
-            Self::StaffOnly => (StatusCode::FORBIDDEN, "Staff-only endpoint").into_response(),
+            ),
+            Self::UsageQuoteExceeded => (
+                RPCErrorCode::UsageQuotaExceeded,
+                "Usage quotas exceeded for this RPC identity, generate another one?",
+            ),
+            Self::MethodNotAllowed => (
+                RPCErrorCode::MethodNotAllowed,
+                "Method not allowed for this RPC identity",
+            ),
+            Self::StaffOnly => (RPCErrorCode::StaffOnly, "Staff-only endpoint"),
+        };
+
+        RPCError {
+            code,
+            message: message.to_string(),
+            retryable: code.retryable(),
+            details: None,
+        }
+    }
+}
+
+pub(super) fn error_status(code: RPCErrorCode) -> StatusCode {
+    match code {
+        RPCErrorCode::InvalidProtocol => StatusCode::PRECONDITION_FAILED,
+        RPCErrorCode::InvalidIdentity | RPCErrorCode::InvalidSignature => StatusCode::UNAUTHORIZED,
+        RPCErrorCode::UsageQuotaExceeded
+        | RPCErrorCode::MethodNotAllowed
+        | RPCErrorCode::OutOfScope
+        | RPCErrorCode::StaffOnly
+        | RPCErrorCode::PermissionDenied
+        | RPCErrorCode::OnboardingRequired => StatusCode::FORBIDDEN,
+        RPCErrorCode::UserNotFound | RPCErrorCode::NotFound => StatusCode::NOT_FOUND,
+        RPCErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        RPCErrorCode::InvalidArgument | RPCErrorCode::MethodFailed => StatusCode::BAD_REQUEST,
+        RPCErrorCode::InvalidState | RPCErrorCode::Aborted => StatusCode::CONFLICT,
+        RPCErrorCode::Discord => StatusCode::BAD_GATEWAY,
+        RPCErrorCode::Database | RPCErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for RPCResponse {
+    fn into_response(self) -> Response {
+        let err = self.error();
+
+        let retry_after = match err.code {
+            RPCErrorCode::RateLimited => err
+                .details
+                .as_ref()
+                .and_then(|d| d.get("retry_after"))
+                .and_then(|r| r.as_u64()),
+            _ => None,
+        };
+
+        let mut resp = (error_status(err.code), Json(err)).into_response();
+
+        if let Some(retry_after) = retry_after {
+            resp.headers_mut()
+                .insert("Retry-After", HeaderValue::from(retry_after));
         }
+
+        resp
     }
 }
 
@@ -92,85 +195,206 @@ impl IntoResponse for Success {
         match self {
             Self::Content(content) => (StatusCode::OK, content).into_response(),
             Self::NoContent => (StatusCode::NO_CONTENT, "").into_response(),
+            Self::DryRun(diff) => (StatusCode::OK, Json(diff)).into_response(),
+            Self::PendingApproval(action_id) => (StatusCode::ACCEPTED, action_id).into_response(),
+            Self::Scheduled(scheduled_id) => (StatusCode::ACCEPTED, scheduled_id).into_response(),
         }
     }
 }
 
+/// Lets the staff panel show the remaining budget of an identity before it runs out
+impl IntoResponseParts for KeychainQuota {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut().insert(
+            "X-RPC-Remaining-Uses",
+            HeaderValue::from(self.remaining_uses),
+        );
+
+        if let Ok(expires_at) = HeaderValue::from_str(&self.expires_at.to_rfc3339()) {
+            res.headers_mut().insert("X-RPC-Expires-At", expires_at);
+        }
+
+        Ok(res)
+    }
+}
+
 pub struct AppState {
     pub cache_http: impls::cache::CacheHttpImpl,
     pub pool: PgPool,
 }
 
+/// Parses the allowed CORS origins from config, ``*`` allows any origin
+///
+/// Invalid origins are logged and skipped, so a typo only locks out that origin
+fn allowed_origins(origins: &[String]) -> AllowOrigin {
+    if origins.iter().any(|o| o == "*") {
+        return AllowOrigin::any();
+    }
+
+    AllowOrigin::list(
+        origins
+            .iter()
+            .filter_map(|o| match o.parse::<HeaderValue>() {
+                Ok(origin) => Some(origin),
+                Err(e) => {
+                    error!("Ignoring invalid RPC CORS origin {}: {}", o, e);
+                    None
+                }
+            }),
+    )
+}
+
 pub async fn rpc_init(pool: PgPool, cache_http: impls::cache::CacheHttpImpl) {
+    let cfg = &crate::config::CONFIG.rpc;
+
+    // Without a pepper, signing keys could be derived by anyone who knows a key ID
+    if cfg.signing_pepper.len() < 32 {
+        error!("Not starting the RPC server: rpc.signing_pepper must be set to a random string of at least 32 characters");
+        return;
+    }
+
     let shared_state = Arc::new(AppState { pool, cache_http });
 
     let app = Router::new()
         .route("/", post(web_rpc_api))
+        .route("/batch", post(web_rpc_batch))
         .route("/actions", get(available_actions))
+        .route("/protocol", get(protocol_info))
+        .route("/events", get(event_feed))
+        .route("/openapi.json", get(openapi_spec))
+        .route("/logs", get(rpc_logs))
+        .route("/logs/export", get(export_logs))
         .with_state(shared_state)
+        .layer(DefaultBodyLimit::max(cfg.max_body_bytes))
         .layer(
             CorsLayer::new()
-                .allow_origin(Any)
+                .allow_origin(allowed_origins(&cfg.allowed_origins))
                 .allow_methods(Any)
-                .allow_headers(Any),
+                .allow_headers(Any)
+                .expose_headers(Any),
         );
 
-    let addr = "127.0.0.1:3010"
-        .parse()
-        .expect("Invalid RPC server address");
+    let res = match (&cfg.tls_cert, &cfg.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = RustlsConfig::from_pem_file(cert, key)
+                .await
+                .expect("Failed to load RPC server TLS certificate");
 
-    info!("Starting RPC server on {}", addr);
+            info!("Starting RPC server on {} (TLS)", cfg.bind);
 
-    if let Err(e) = axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-    {
+            axum_server::bind_rustls(cfg.bind, tls)
+                .serve(app.into_make_service())
+                .await
+        }
+        (None, None) => {
+            info!("Starting RPC server on {}", cfg.bind);
+
+            axum_server::bind(cfg.bind)
+                .serve(app.into_make_service())
+                .await
+        }
+        _ => panic!("Both rpc.tls_cert and rpc.tls_key must be set to serve RPC over TLS"),
+    };
+
+    if let Err(e) = res {
         panic!("RPC server error: {}", e);
     }
 }
 
-async fn web_rpc_api(
-    State(state): State<Arc<AppState>>,
-    Json(req): Json<RPCRequest>,
-) -> Result<Success, RPCResponse> {
-    if req.protocol != 5 {
-        return Err(RPCResponse::InvalidProtocol);
+/// Checks that a user exists and is staff
+async fn ensure_staff(state: &AppState, user_id: &str) -> Result<(), RPCResponse> {
+    let check = sqlx::query!("SELECT staff FROM users WHERE user_id = $1", user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|_| RPCResponse::UserNotFound)?;
+
+    if !check.staff {
+        return Err(RPCResponse::StaffOnly);
     }
 
-    // Check RPC key
-    let keychain = RPC_KEYCHAIN.get(&req.rpc_identity);
+    Ok(())
+}
 
-    if keychain.is_none() {
-        return Err(RPCResponse::InvalidIdentity);
-    }
+/// Checks the signature of a request, returning the identity it was signed with
+///
+/// ``payload`` is the request body, or the raw query string for ``GET`` requests
+async fn signed_identity(
+    state: &AppState,
+    signature: &Signature,
+    method: &str,
+    path: &str,
+    payload: &[u8],
+) -> Result<KeychainData, RPCResponse> {
+    let keychain = KeychainData::get(&state.pool, &signature.key_id)
+        .await
+        .map_err(|e| RPCResponse::Err(e.to_string()))?
+        .ok_or(RPCResponse::InvalidIdentity)?;
+
+    signature
+        .verify(
+            &signing::signing_key(&keychain.key_id),
+            method,
+            path,
+            payload,
+            Utc::now().timestamp(),
+        )
+        .map_err(RPCResponse::Method)?;
 
-    let keychain = keychain.unwrap();
+    signature
+        .consume_nonce(&state.pool)
+        .await
+        .map_err(RPCResponse::Method)?;
 
-    // Ensure it matches user
-    if keychain.user_id != req.user_id {
+    // POST requests go on to consume a use in the database, which fails once revoked. GET requests
+    // do not, so check the database here as the cache can miss a revocation made on another instance
+    if method == "GET"
+        && !KeychainData::is_active(&state.pool, &keychain.key_id)
+            .await
+            .map_err(|e| RPCResponse::Err(e.to_string()))?
+    {
         return Err(RPCResponse::InvalidIdentity);
     }
 
-    // Check usage limits
-    if keychain.used > keychain.max_uses {
-        return Err(RPCResponse::UsageQuoteExceeded);
-    }
+    ensure_staff(state, &keychain.user_id).await?;
 
-    // Get name of method
-    if !keychain.allowed_methods.contains(&req.method.to_string()) {
-        return Err(RPCResponse::MethodNotAllowed);
-    }
+    Ok(keychain)
+}
 
-    // Increment used
-    RPC_KEYCHAIN.insert(req.rpc_identity,  KeychainData {
-        used: keychain.used + 1,
-        ..keychain
-    }).await;
+/// The credentials sent in the body of an unsigned request, see ``protocol::LEGACY_AUTH_PROTOCOL``
+#[derive(Deserialize)]
+struct LegacyCredentials {
+    user_id: String,
+    protocol: u8,
+    api_token: String,
+    rpc_identity: String,
+}
+
+/// Checks the API token and RPC identity of an unsigned request from an old client
+///
+/// Returns ``None`` if the body does not carry them or its protocol must sign requests
+async fn legacy_identity(
+    state: &AppState,
+    body: &[u8],
+) -> Result<Option<KeychainData>, RPCResponse> {
+    let creds = match serde_json::from_slice::<LegacyCredentials>(body) {
+        Ok(creds) if creds.protocol <= protocol::LEGACY_AUTH_PROTOCOL => creds,
+        _ => return Ok(None),
+    };
+
+    let key_id =
+        signing::identity_key_id(&creds.rpc_identity).ok_or(RPCResponse::InvalidIdentity)?;
+
+    let keychain = KeychainData::get(&state.pool, key_id)
+        .await
+        .map_err(|e| RPCResponse::Err(e.to_string()))?
+        .ok_or(RPCResponse::InvalidIdentity)?;
 
     let check = sqlx::query!(
         "SELECT staff FROM users WHERE user_id = $1 AND api_token = $2",
-        &req.user_id,
-        &req.api_token
+        &creds.user_id,
+        &creds.api_token
     )
     .fetch_one(&state.pool)
     .await
@@ -180,165 +404,231 @@ async fn web_rpc_api(
         return Err(RPCResponse::StaffOnly);
     }
 
-    match req
-        .method
-        .handle(RPCHandle {
-            cache_http: state.cache_http.clone(),
-            pool: state.pool.clone(),
-            user_id: req.user_id,
-        })
-        .await
-        .map_err(|e| RPCResponse::Err(e.to_string()))?
+    Ok(Some(keychain))
+}
+
+/// Checks the signature of a ``POST`` request and parses its body
+///
+/// Unsigned requests are only accepted from clients still on ``protocol::LEGACY_AUTH_PROTOCOL``
+async fn signed_body<T: DeserializeOwned>(
+    state: &AppState,
+    headers: &HeaderMap,
+    uri: &Uri,
+    body: &Bytes,
+) -> Result<(KeychainData, T), RPCResponse> {
+    let keychain = match Signature::from_headers(headers) {
+        Ok(signature) => signed_identity(state, &signature, "POST", uri.path(), body).await?,
+        Err(e) => legacy_identity(state, body)
+            .await?
+            .ok_or(RPCResponse::Method(e))?,
+    };
+
+    let req = serde_json::from_slice(body).map_err(|e| {
+        RPCResponse::Method(RPCFailure::invalid_argument(format!("Invalid request: {}", e)).into())
+    })?;
+
+    Ok((keychain, req))
+}
+
+/// Checks the user and methods of a request and consumes one use of its identity
+///
+/// Dry runs change nothing, so they do not consume a use
+async fn authorize(
+    state: &AppState,
+    keychain: &KeychainData,
+    user_id: &str,
+    protocol_version: u8,
+    methods: &[RPCMethod],
+    dry_run: bool,
+) -> Result<KeychainQuota, RPCResponse> {
+    if !protocol::is_supported(protocol_version)
+        || methods.iter().any(|m| m.min_protocol() > protocol_version)
     {
-        RPCSuccess::Content(content) => Ok(Success::Content(content)),
-        RPCSuccess::NoContent => Ok(Success::NoContent),
+        return Err(RPCResponse::InvalidProtocol);
     }
-}
 
-#[derive(Serialize, TS)]
-#[ts(export, export_to = ".generated/RPCWebField.ts")]
-struct WebField {
-    id: String,
-    label: String,
-    field_type: FieldType,
-    icon: String,
-    placeholder: String,
-}
-
-impl WebField {
-    fn bot_id() -> Self {
-        WebField {
-            id: "bot_id".to_string(),
-            label: "Bot ID".to_string(),
-            field_type: FieldType::Text,
-            icon: "ic:twotone-access-time-filled".to_string(),
-            placeholder: "The Bot ID to perform the action on".to_string(),
+    // Ensure it matches user
+    if keychain.user_id != user_id {
+        return Err(RPCResponse::InvalidIdentity);
+    }
+
+    // Get name of method
+    if methods
+        .iter()
+        .any(|m| !keychain.allowed_methods.contains(&m.to_string()))
+    {
+        return Err(RPCResponse::MethodNotAllowed);
+    }
+
+    for method in methods {
+        keychain
+            .scope
+            .check(&state.pool, method)
+            .await
+            .map_err(RPCResponse::Method)?;
+    }
+
+    if dry_run {
+        if !KeychainData::is_active(&state.pool, &keychain.key_id)
+            .await
+            .map_err(|e| RPCResponse::Err(e.to_string()))?
+        {
+            return Err(RPCResponse::InvalidIdentity);
         }
+
+        return Ok(KeychainQuota {
+            remaining_uses: keychain.max_uses - keychain.used,
+            expires_at: keychain.expires_at,
+        });
     }
 
-    fn reason() -> Self {
-        WebField {
-            id: "reason".to_string(),
-            label: "Reason".to_string(),
-            field_type: FieldType::Textarea,
-            icon: "material-symbols:question-mark".to_string(),
-            placeholder: "Reason for performing this action".to_string(),
+    // Consume a use, this is done in the database so concurrent requests can't race past max_uses
+    let quota = KeychainData::consume_use(&state.pool, &keychain.key_id)
+        .await
+        .map_err(|e| RPCResponse::Err(e.to_string()))?;
+
+    match quota {
+        Some(quota) => Ok(quota),
+        // Revoked or expired since it was cached
+        None if !KeychainData::is_active(&state.pool, &keychain.key_id)
+            .await
+            .map_err(|e| RPCResponse::Err(e.to_string()))? =>
+        {
+            Err(RPCResponse::InvalidIdentity)
         }
+        None => Err(RPCResponse::UsageQuoteExceeded),
     }
 }
 
-#[derive(Serialize, TS)]
-#[ts(export, export_to = ".generated/RPCFieldType.ts")]
-enum FieldType {
-    Text,
-    Textarea,
-    Number,
-    Hour, // Time expressed as a number of hours
-    Boolean,
-}
-
-// Returns a set of WebField's for a given enum variant
-fn method_web_fields(method: RPCMethod) -> Vec<WebField> {
-    match method {
-        RPCMethod::BotClaim { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "force".to_string(),
-                label: "Force claim bot?".to_string(),
-                field_type: FieldType::Boolean,
-                icon: "fa-solid:sign-out-alt".to_string(),
-                placeholder: "Yes/No".to_string(),
-            },
-        ],
-        RPCMethod::BotUnclaim { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotApprove { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotDeny { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotVoteReset { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotVoteResetAll { .. } => vec![WebField::reason()],
-        RPCMethod::BotUnverify { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotPremiumAdd { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "time_period_hours".to_string(),
-                label: "Time [X unit(s)]".to_string(),
-                field_type: FieldType::Hour,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "Time period. Format: X years/days/hours".to_string(),
-            },
-            WebField::reason(),
-        ],
-        RPCMethod::BotPremiumRemove { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotVoteBanAdd { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotVoteBanRemove { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotForceRemove { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "kick".to_string(),
-                label: "Kick the bot from the server".to_string(),
-                field_type: FieldType::Boolean,
-                icon: "fa-solid:sign-out-alt".to_string(),
-                placeholder: "Kick the bot from the server".to_string(),
-            },
-            WebField::reason(),
-        ],
-        RPCMethod::BotCertifyAdd { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotCertifyRemove { .. } => vec![WebField::bot_id(), WebField::reason()],
-        RPCMethod::BotVoteCountSet { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "count".to_string(),
-                label: "Vote count".to_string(),
-                field_type: FieldType::Number,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "Vote count".to_string(),
-            },
-            WebField::reason(),
-        ],
-        RPCMethod::BotTransferOwnershipUser { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "new_owner".to_string(),
-                label: "User ID".to_string(),
-                field_type: FieldType::Text,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "New Owner".to_string(),
-            },
-            WebField::reason(),
-        ],
-        RPCMethod::BotTransferOwnershipTeam { .. } => vec![
-            WebField::bot_id(),
-            WebField {
-                id: "new_team".to_string(),
-                label: "Team ID".to_string(),
-                field_type: FieldType::Text,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "New Team".to_string(),
-            },
-            WebField::reason(),
-        ],
-        RPCMethod::TeamNameEdit { .. } => vec![
-            WebField {
-                id: "team_id".to_string(),
-                label: "Team ID".to_string(),
-                field_type: FieldType::Text,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "Team ID".to_string(),
-            },
-            WebField {
-                id: "new_name".to_string(),
-                label: "New team name".to_string(),
-                field_type: FieldType::Text,
-                icon: "material-symbols:timer".to_string(),
-                placeholder: "Team name".to_string(),
+async fn web_rpc_api(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Result<Response, RPCResponse> {
+    let (keychain, req) = signed_body::<RPCRequest>(&state, &headers, &uri, &body).await?;
+
+    let quota = authorize(
+        &state,
+        &keychain,
+        &req.user_id,
+        req.protocol,
+        std::slice::from_ref(&req.method),
+        req.dry_run,
+    )
+    .await?;
+
+    if let Some(execute_at) = req.execute_at {
+        if req.dry_run {
+            return Err(RPCResponse::Method(
+                RPCFailure::invalid_argument("Dry runs cannot be scheduled").into(),
+            ));
+        }
+
+        let resp = super::scheduled::schedule(
+            &RPCHandle {
+                cache_http: state.cache_http.clone(),
+                pool: state.pool.clone(),
+                user_id: req.user_id,
+                dry_run: false,
+                approved_by: None,
             },
-            WebField::reason(),
-        ],
+            &req.method,
+            execute_at,
+        )
+        .await
+        .map(|id| Success::Scheduled(id.to_string()))
+        .map_err(RPCResponse::Method);
+
+        return Ok((quota, ProtocolHeaders(req.protocol), resp).into_response());
     }
+
+    let resp = match req
+        .method
+        .handle(RPCHandle {
+            cache_http: state.cache_http.clone(),
+            pool: state.pool.clone(),
+            user_id: req.user_id,
+            dry_run: req.dry_run,
+            approved_by: None,
+        })
+        .await
+    {
+        Ok(RPCSuccess::Content(content)) => Ok(Success::Content(content)),
+        Ok(RPCSuccess::NoContent) => Ok(Success::NoContent),
+        Ok(RPCSuccess::DryRun(diff)) => Ok(Success::DryRun(diff)),
+        Ok(RPCSuccess::PendingApproval(action_id)) => Ok(Success::PendingApproval(action_id)),
+        Err(e) => Err(RPCResponse::Method(e)),
+    };
+
+    Ok((quota, ProtocolHeaders(req.protocol), resp).into_response())
 }
 
-#[derive(Serialize, TS)]
+/// Runs several methods under one identity, charging a single use of it
+async fn web_rpc_batch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Result<Response, RPCResponse> {
+    let (keychain, req) = signed_body::<RPCBatchRequest>(&state, &headers, &uri, &body).await?;
+
+    // Before authorizing, as that checks the scope of every method
+    super::batch::check_size(&req.methods).map_err(RPCResponse::Method)?;
+
+    let quota = authorize(
+        &state,
+        &keychain,
+        &req.user_id,
+        req.protocol,
+        &req.methods,
+        req.dry_run,
+    )
+    .await?;
+
+    let results = super::batch::handle_batch(
+        &req.methods,
+        RPCHandle {
+            cache_http: state.cache_http.clone(),
+            pool: state.pool.clone(),
+            user_id: req.user_id,
+            dry_run: req.dry_run,
+            approved_by: None,
+        },
+        req.atomic,
+    )
+    .await
+    .map_err(RPCResponse::Method)?;
+
+    let outcomes = req
+        .methods
+        .iter()
+        .zip(results)
+        .map(|(method, res)| RPCBatchOutcome {
+            method: method.to_string(),
+            result: match res {
+                Ok(RPCSuccess::Content(content)) => RPCBatchResult::Success {
+                    content: Some(content),
+                },
+                Ok(RPCSuccess::NoContent) => RPCBatchResult::Success { content: None },
+                Ok(RPCSuccess::DryRun(diff)) => RPCBatchResult::DryRun { diff },
+                Ok(RPCSuccess::PendingApproval(action_id)) => {
+                    RPCBatchResult::PendingApproval { action_id }
+                }
+                Err(e) => RPCBatchResult::Error {
+                    error: RPCError::from(&e),
+                },
+            },
+        })
+        .collect::<Vec<_>>();
+
+    Ok((quota, ProtocolHeaders(req.protocol), Json(outcomes)).into_response())
+}
+
+#[derive(Serialize, TS, JsonSchema)]
 #[ts(export, export_to = ".generated/RPCWebAction.ts")]
-struct WebAction {
+#[schemars(rename = "RPCWebAction")]
+pub(super) struct WebAction {
     id: String,
     label: String,
     description: String,
@@ -347,6 +637,251 @@ struct WebAction {
     fields: Vec<WebField>,
 }
 
+#[derive(Serialize, TS)]
+#[ts(export, export_to = ".generated/RPCProtocolVersion.ts")]
+struct ProtocolVersion {
+    version: u8,
+    deprecated: bool,
+    methods: Vec<MethodSchema>,
+}
+
+#[derive(Serialize, TS)]
+#[ts(export, export_to = ".generated/RPCMethodSchema.ts")]
+struct MethodSchema {
+    id: String,
+    needed_perms: RPCPerms,
+    fields: Vec<WebField>,
+}
+
+#[derive(Serialize, TS)]
+#[ts(export, export_to = ".generated/RPCProtocolInfo.ts")]
+struct ProtocolInfo {
+    current: u8,
+    min_supported: u8,
+    versions: Vec<ProtocolVersion>,
+}
+
+/// Advertises the supported protocol versions and the methods each one supports
+async fn protocol_info() -> Result<Json<ProtocolInfo>, RPCResponse> {
+    let mut versions = Vec::new();
+
+    for version in protocol::MIN_PROTOCOL..=protocol::CURRENT_PROTOCOL {
+        let mut methods = Vec::new();
+
+        for variant in super::core::RPCMethod::VARIANTS {
+            let method = super::core::RPCMethod::from_str(variant)
+                .map_err(|e| RPCResponse::Err(e.to_string()))?;
+
+            if method.min_protocol() > version {
+                continue;
+            }
+
+            methods.push(MethodSchema {
+                id: variant.to_string(),
+                needed_perms: method.needs_perms(),
+                fields: method.spec().fields,
+            });
+        }
+
+        versions.push(ProtocolVersion {
+            version,
+            deprecated: protocol::is_deprecated(version),
+            methods,
+        });
+    }
+
+    Ok(Json(ProtocolInfo {
+        current: protocol::CURRENT_PROTOCOL,
+        min_supported: protocol::MIN_PROTOCOL,
+        versions,
+    }))
+}
+
+/// Authenticates a staff panel user from a signed ``GET`` request
+///
+/// Also checks that the user has at least ``perms``, returning their user ID
+async fn panel_user(
+    state: &AppState,
+    headers: &HeaderMap,
+    uri: &Uri,
+    perms: RPCPerms,
+) -> Result<String, RPCResponse> {
+    let signature = Signature::from_headers(headers).map_err(RPCResponse::Method)?;
+
+    let keychain = signed_identity(
+        state,
+        &signature,
+        "GET",
+        uri.path(),
+        uri.query().unwrap_or_default().as_bytes(),
+    )
+    .await?;
+
+    perms
+        .check(&state.pool, &keychain.user_id)
+        .await
+        .map_err(RPCResponse::Method)?;
+
+    Ok(keychain.user_id)
+}
+
+/// Queries ``rpc_logs``, newest first, for Head staff and above
+async fn rpc_logs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(filter): Query<LogFilter>,
+) -> Result<Json<LogPage>, RPCResponse> {
+    panel_user(&state, &headers, &uri, RPCPerms::Head).await?;
+
+    logs::query(&state.pool, &filter)
+        .await
+        .map(Json)
+        .map_err(RPCResponse::Method)
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// First day to export
+    from: NaiveDate,
+    /// Last day to export, inclusive
+    to: NaiveDate,
+    user_id: Option<String>,
+    format: ExportFormat,
+    /// Return only the totals of the export as JSON instead of the file
+    #[serde(default)]
+    summary: bool,
+}
+
+/// Exports ``rpc_logs`` and ``staff_general_logs`` for a date range as a CSV or NDJSON file, for Head staff and above
+///
+/// The file is streamed as rows are read from the database. If reading fails part way the response is cut short
+async fn export_logs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, RPCResponse> {
+    panel_user(&state, &headers, &uri, RPCPerms::Head).await?;
+
+    let range = ExportRange::new(query.from, query.to).map_err(RPCResponse::Method)?;
+
+    if query.summary {
+        return logs::export_summary(&state.pool, range, query.user_id)
+            .await
+            .map(|summary| Json(summary).into_response())
+            .map_err(RPCResponse::Method);
+    }
+
+    let format = query.format;
+    let rows = logs::export(state.pool.clone(), range, query.user_id);
+
+    let lines = futures_util::stream::unfold(rows, move |mut rows| async move {
+        let line = rows.recv().await?.and_then(|row| {
+            let mut line = Vec::new();
+            row.write(format, &mut line)?;
+            Ok(line)
+        });
+
+        Some((line, rows))
+    });
+
+    let body = futures_util::stream::once(async move { Ok(format.header().to_vec()) }).chain(lines);
+
+    Ok((
+        [
+            ("Content-Type", format.content_type().to_string()),
+            (
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", range.filename(format)),
+            ),
+        ],
+        StreamBody::new(body),
+    )
+        .into_response())
+}
+
+/// Serves the OpenAPI 3 spec of this server, for clients that cannot use the generated TypeScript types
+async fn openapi_spec() -> Result<Json<serde_json::Value>, RPCResponse> {
+    super::openapi::spec()
+        .map(Json)
+        .map_err(|e| RPCResponse::Err(e.to_string()))
+}
+
+/// ``EventSource`` cannot send headers, so the signature is sent in the query string instead
+///
+/// The signed message is the same as for other ``GET`` requests, with an empty query string
+#[derive(Deserialize)]
+struct EventFeedQuery {
+    key: String,
+    timestamp: i64,
+    nonce: String,
+    signature: String,
+}
+
+/// How often an open event feed checks that its identity is still valid
+const EVENT_FEED_RECHECK: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The state of one open ``/events`` stream
+struct EventFeed {
+    rx: broadcast::Receiver<events::RPCEvent>,
+    recheck: tokio::time::Interval,
+    pool: PgPool,
+    key_id: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Streams moderation events to the staff panel as Server-Sent Events
+async fn event_feed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventFeedQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, RPCResponse> {
+    let signature = Signature {
+        key_id: query.key,
+        timestamp: query.timestamp,
+        nonce: query.nonce,
+        signature: query.signature,
+    };
+
+    let keychain = signed_identity(&state, &signature, "GET", "/events", &[]).await?;
+
+    let feed = EventFeed {
+        rx: events::subscribe(),
+        recheck: tokio::time::interval(EVENT_FEED_RECHECK),
+        pool: state.pool.clone(),
+        key_id: signature.key_id,
+        expires_at: keychain.expires_at,
+    };
+
+    // The stream ends once the identity expires or is revoked, the panel then has to sign in again
+    let stream = futures_util::stream::unfold(feed, |mut feed| async move {
+        loop {
+            tokio::select! {
+                res = feed.rx.recv() => match res {
+                    Ok(event) => {
+                        return Some((Event::default().event(event.name()).json_data(&event), feed))
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Event feed subscriber lagged behind by {} events", missed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = feed.recheck.tick() => {
+                    if feed.expires_at <= Utc::now()
+                        || !KeychainData::is_active(&feed.pool, &feed.key_id)
+                            .await
+                            .unwrap_or(false)
+                    {
+                        return None;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 #[derive(Deserialize)]
 struct WebActionQuery {
     user_id: Option<String>,
@@ -396,7 +931,7 @@ async fn available_actions(
             description: method.description(),
             needed_perms: method.needs_perms(),
             method_example: method.clone(),
-            fields: method_web_fields(method),
+            fields: method.spec().fields,
         };
 
         match action.needed_perms {
@@ -0,0 +1,246 @@
+use axum::http::HeaderMap;
+use data_encoding::HEXLOWER;
+use ring::hmac;
+use sqlx::PgPool;
+
+use super::error::{RPCErrorCode, RPCFailure};
+use crate::config::CONFIG;
+use crate::Error;
+
+/// Header containing the key ID of the RPC identity
+pub const KEY_HEADER: &str = "X-RPC-Key";
+
+/// Header containing the unix timestamp the request was signed at
+pub const TIMESTAMP_HEADER: &str = "X-RPC-Timestamp";
+
+/// Header containing a random string that is unique to this request
+pub const NONCE_HEADER: &str = "X-RPC-Nonce";
+
+/// Header containing the HMAC-SHA256 signature of the request, as ``sha256=<hex>``
+pub const SIGNATURE_HEADER: &str = "X-RPC-Signature";
+
+/// How far the timestamp of a request may be from the server clock
+pub const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+/// Prefix of the identity file handed to the user
+pub const IDENTITY_PREFIX: &str = "Bluejay$V1:";
+
+/// Nonces must be between these lengths
+const NONCE_LEN: std::ops::RangeInclusive<usize> = 16..=128;
+
+/// Derives the signing key of an RPC identity from its key ID
+///
+/// The key is derived from ``rpc.signing_pepper`` and never stored, so the database alone cannot sign requests.
+/// The identity file handed to the user is ``Bluejay$V1:<key ID>:<signing key>``
+pub fn signing_key(key_id: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, CONFIG.rpc.signing_pepper.as_bytes());
+    let tag = hmac::sign(
+        &key,
+        format!("arcadia-rpc-signing-v1:{}", key_id).as_bytes(),
+    );
+
+    HEXLOWER.encode(tag.as_ref())
+}
+
+/// Builds the identity file handed to the user for a key ID
+pub fn identity(key_id: &str) -> String {
+    format!("{}{}:{}", IDENTITY_PREFIX, key_id, signing_key(key_id))
+}
+
+/// Checks a whole identity file, returning its key ID if the signing key in it is valid
+///
+/// Only used for protocol 5 clients, which send the identity in the request body instead of signing requests
+pub fn identity_key_id(identity: &str) -> Option<&str> {
+    let (key_id, key) = identity.strip_prefix(IDENTITY_PREFIX)?.split_once(':')?;
+
+    ring::constant_time::verify_slices_are_equal(signing_key(key_id).as_bytes(), key.as_bytes())
+        .ok()?;
+
+    Some(key_id)
+}
+
+/// The message that is signed: ``<timestamp>\n<nonce>\n<HTTP method>\n<path>\n<body>``
+///
+/// For ``GET`` requests the raw query string is signed in place of the body
+fn message(timestamp: i64, nonce: &str, method: &str, path: &str, body: &[u8]) -> Vec<u8> {
+    let mut msg = format!("{}\n{}\n{}\n{}\n", timestamp, nonce, method, path).into_bytes();
+    msg.extend_from_slice(body);
+    msg
+}
+
+fn invalid(message: &str) -> Error {
+    RPCFailure::new(RPCErrorCode::InvalidSignature, message).into()
+}
+
+/// The signature of an RPC request
+pub struct Signature {
+    /// The key ID of the identity the request was signed with
+    pub key_id: String,
+    pub timestamp: i64,
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl Signature {
+    /// Reads the signature from the ``X-RPC-*`` headers
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, Error> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+                .ok_or_else(|| invalid("This request is not signed"))
+        };
+
+        Ok(Signature {
+            key_id: header(KEY_HEADER)?,
+            timestamp: header(TIMESTAMP_HEADER)?
+                .parse()
+                .map_err(|_| invalid("Invalid request timestamp"))?,
+            nonce: header(NONCE_HEADER)?,
+            signature: header(SIGNATURE_HEADER)?,
+        })
+    }
+
+    /// Checks the timestamp and nonce of the request and the signature against the signing key of its identity
+    ///
+    /// This does not check that the nonce is unused, see ``consume_nonce``
+    pub fn verify(
+        &self,
+        signing_key: &str,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: i64,
+    ) -> Result<(), Error> {
+        if (now - self.timestamp).abs() > MAX_CLOCK_SKEW_SECS {
+            return Err(invalid(
+                "This request has expired, check that your clock is correct",
+            ));
+        }
+
+        if !NONCE_LEN.contains(&self.nonce.len()) {
+            return Err(invalid("Invalid request nonce"));
+        }
+
+        let tag = self
+            .signature
+            .strip_prefix("sha256=")
+            .and_then(|sig| HEXLOWER.decode(sig.to_lowercase().as_bytes()).ok())
+            .ok_or_else(|| invalid("Invalid request signature"))?;
+
+        let key = hmac::Key::new(hmac::HMAC_SHA256, signing_key.as_bytes());
+
+        hmac::verify(
+            &key,
+            &message(self.timestamp, &self.nonce, method, path, body),
+            &tag,
+        )
+        .map_err(|_| invalid("Invalid request signature"))
+    }
+
+    /// Records the nonce of a verified request in ``rpc_nonces``, failing if it was already used
+    ///
+    /// This is only done once the signature is valid, so unsigned requests cannot burn the nonces of real ones.
+    /// The table is shared by all instances, so a request can never be replayed against another one
+    pub async fn consume_nonce(&self, pool: &PgPool) -> Result<(), Error> {
+        let res = sqlx::query!(
+            "INSERT INTO rpc_nonces (key_id, nonce) VALUES ($1, $2) ON CONFLICT (key_id, nonce) DO NOTHING",
+            &self.key_id,
+            &self.nonce
+        )
+        .execute(pool)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(invalid("This request has already been made"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Deletes nonces old enough that their requests are rejected by their timestamp anyways
+pub async fn prune_nonces(pool: &PgPool) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM rpc_nonces WHERE created_at < NOW() - make_interval(secs => $1)",
+        (2 * MAX_CLOCK_SKEW_SECS) as f64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "signing-key";
+    const NONCE: &str = "0123456789abcdef";
+    const NOW: i64 = 1_700_000_000;
+
+    fn signed(timestamp: i64, nonce: &str, method: &str, path: &str, body: &[u8]) -> Signature {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, KEY.as_bytes());
+        let tag = hmac::sign(&key, &message(timestamp, nonce, method, path, body));
+
+        Signature {
+            key_id: "key".to_string(),
+            timestamp,
+            nonce: nonce.to_string(),
+            signature: format!("sha256={}", HEXLOWER.encode(tag.as_ref())),
+        }
+    }
+
+    #[test]
+    fn verify_accepts_valid_signature() {
+        let sig = signed(NOW, NONCE, "POST", "/", b"{}");
+
+        assert!(sig.verify(KEY, "POST", "/", b"{}", NOW).is_ok());
+        assert!(sig
+            .verify(KEY, "POST", "/", b"{}", NOW + MAX_CLOCK_SKEW_SECS)
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_accepts_uppercase_hex() {
+        let mut sig = signed(NOW, NONCE, "POST", "/", b"{}");
+        sig.signature = format!("sha256={}", sig.signature[7..].to_uppercase());
+
+        assert!(sig.verify(KEY, "POST", "/", b"{}", NOW).is_ok());
+    }
+
+    #[test]
+    fn verify_rejects_tampering() {
+        let sig = signed(NOW, NONCE, "POST", "/", b"{}");
+
+        assert!(sig.verify("other-key", "POST", "/", b"{}", NOW).is_err());
+        assert!(sig.verify(KEY, "GET", "/", b"{}", NOW).is_err());
+        assert!(sig.verify(KEY, "POST", "/batch", b"{}", NOW).is_err());
+        assert!(sig.verify(KEY, "POST", "/", b"{\"a\":1}", NOW).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_stale_timestamps() {
+        let sig = signed(NOW, NONCE, "POST", "/", b"{}");
+
+        assert!(sig
+            .verify(KEY, "POST", "/", b"{}", NOW + MAX_CLOCK_SKEW_SECS + 1)
+            .is_err());
+        assert!(sig
+            .verify(KEY, "POST", "/", b"{}", NOW - MAX_CLOCK_SKEW_SECS - 1)
+            .is_err());
+    }
+
+    #[test]
+    fn verify_rejects_bad_nonces_and_formats() {
+        assert!(signed(NOW, "short", "POST", "/", b"{}")
+            .verify(KEY, "POST", "/", b"{}", NOW)
+            .is_err());
+
+        let mut sig = signed(NOW, NONCE, "POST", "/", b"{}");
+        sig.signature = sig.signature.replace("sha256=", "");
+
+        assert!(sig.verify(KEY, "POST", "/", b"{}", NOW).is_err());
+    }
+}
@@ -0,0 +1,727 @@
+use std::num::NonZeroU64;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::types::Uuid;
+use strum_macros::EnumIter;
+use ts_rs::TS;
+
+use super::core::{RPCMethod, RPCPerms};
+use super::error::RPCFailure;
+use super::protocol;
+use crate::Error;
+
+/// The most bots a bulk method can act on at once
+pub const MAX_BULK_BOTS: usize = 50;
+
+const BULK_REASON_PLACEHOLDER: &str =
+    "Reason for each bot, {bot}, {bot_id} and {owner} are replaced with the bot, its ID and its owner";
+
+/// Everything the web panel and the ``/rpc`` modal need to know about a method
+///
+/// This is the only place a new method needs to be described, ``label``, ``description``,
+/// ``needs_perms`` and the web/modal fields are all read from here
+pub struct MethodSpec {
+    pub label: &'static str,
+    pub description: &'static str,
+    pub perms: RPCPerms,
+    /// The oldest RPC protocol version that supports this method
+    pub min_protocol: u8,
+    /// The arguments of the method, in the order they are asked for
+    pub fields: Vec<WebField>,
+}
+
+impl MethodSpec {
+    fn new(label: &'static str, description: &'static str, perms: RPCPerms) -> Self {
+        Self {
+            label,
+            description,
+            perms,
+            min_protocol: protocol::MIN_PROTOCOL,
+            fields: Vec::new(),
+        }
+    }
+
+    /// New methods should set the protocol version they were added in here
+    fn since(mut self, min_protocol: u8) -> Self {
+        self.min_protocol = min_protocol;
+        self
+    }
+
+    fn field(mut self, field: WebField) -> Self {
+        self.fields.push(field);
+        self
+    }
+}
+
+#[derive(Serialize, TS, JsonSchema, Clone)]
+#[ts(export, export_to = ".generated/RPCWebField.ts")]
+#[schemars(rename = "RPCWebField")]
+pub struct WebField {
+    /// The name of the argument in ``RPCMethod``
+    pub id: String,
+    pub label: String,
+    pub field_type: FieldType,
+    pub icon: String,
+    pub placeholder: String,
+    /// Whether the field must be filled in, optional fields are ``null`` when left empty
+    pub required: bool,
+    /// Checked by the server before the method runs, clients should check these too
+    pub validation: Vec<FieldValidation>,
+}
+
+impl WebField {
+    pub fn new(
+        id: &str,
+        label: &str,
+        field_type: FieldType,
+        icon: &str,
+        placeholder: &str,
+    ) -> Self {
+        WebField {
+            id: id.to_string(),
+            label: label.to_string(),
+            field_type,
+            icon: icon.to_string(),
+            placeholder: placeholder.to_string(),
+            required: true,
+            validation: Vec::new(),
+        }
+    }
+
+    fn bot_id() -> Self {
+        WebField::new(
+            "bot_id",
+            "Bot ID",
+            FieldType::Text,
+            "ic:twotone-access-time-filled",
+            "The Bot ID to perform the action on",
+        )
+        .check(FieldValidation::Snowflake)
+    }
+
+    fn reason() -> Self {
+        WebField::new(
+            "reason",
+            "Reason",
+            FieldType::Textarea,
+            "material-symbols:question-mark",
+            "Reason for performing this action",
+        )
+        // Reasons end up in embed fields, which can be at most 1024 characters long
+        .check(FieldValidation::Length { min: 0, max: 1024 })
+    }
+
+    fn bot_ids() -> Self {
+        WebField::new(
+            "bot_ids",
+            "Bot IDs",
+            FieldType::TextList,
+            "material-symbols:format-list-bulleted",
+            "The Bot IDs to perform the action on, one per line",
+        )
+        .check(FieldValidation::Snowflake)
+        .check(FieldValidation::Items {
+            min: 1,
+            max: MAX_BULK_BOTS,
+        })
+    }
+
+    fn bulk_reasons() -> Self {
+        WebField::new(
+            "reasons",
+            "Reasons for single bots",
+            FieldType::TextMap,
+            "material-symbols:edit-note",
+            "Optional, one <bot ID>: <reason> per line to use instead of the reason above for those bots",
+        )
+        .optional()
+        .check(FieldValidation::Length { min: 0, max: 1024 })
+    }
+
+    fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    fn with_placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+
+    fn check(mut self, validation: FieldValidation) -> Self {
+        self.validation.push(validation);
+        self
+    }
+
+    /// Checks a value of this field against its validation spec
+    fn validate(&self, value: &Value) -> Result<(), String> {
+        if !self.required && value.is_null() {
+            return Ok(());
+        }
+
+        for validation in &self.validation {
+            if let FieldValidation::Items { min, max } = validation {
+                let len = value.as_array().map(|v| v.len()).unwrap_or(0);
+
+                if len < *min || len > *max {
+                    return Err(format!("must have between {} and {} entries", min, max));
+                }
+
+                continue;
+            }
+
+            // Every other rule applies to each entry of a list or map
+            match value {
+                Value::Array(items) => {
+                    for item in items {
+                        validation.check_value(item)?;
+                    }
+                }
+                Value::Object(items) => {
+                    for item in items.values() {
+                        validation.check_value(item)?;
+                    }
+                }
+                _ => validation.check_value(value)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A rule the value of a field must follow
+#[derive(Serialize, TS, JsonSchema, Clone)]
+#[ts(export, export_to = ".generated/RPCFieldValidation.ts")]
+#[schemars(rename = "RPCFieldValidation")]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FieldValidation {
+    /// A Discord ID
+    Snowflake,
+    Uuid,
+    /// Length of a string in characters, inclusive
+    Length {
+        min: usize,
+        max: usize,
+    },
+    /// Length of a string in bytes, inclusive
+    ByteLength {
+        min: usize,
+        max: usize,
+    },
+    /// Bounds of a number, inclusive
+    Range {
+        min: i64,
+        max: i64,
+    },
+    /// Number of entries in a list, inclusive
+    Items {
+        min: usize,
+        max: usize,
+    },
+}
+
+impl FieldValidation {
+    /// Checks a single value against this rule
+    fn check_value(&self, value: &Value) -> Result<(), String> {
+        match self {
+            FieldValidation::Snowflake => {
+                let valid = value
+                    .as_str()
+                    .map(|v| (17..=20).contains(&v.len()) && v.parse::<NonZeroU64>().is_ok())
+                    .unwrap_or(false);
+
+                if !valid {
+                    return Err("must be a Discord ID".to_string());
+                }
+            }
+            FieldValidation::Uuid => {
+                let valid = value
+                    .as_str()
+                    .map(|v| v.parse::<Uuid>().is_ok())
+                    .unwrap_or(false);
+
+                if !valid {
+                    return Err("must be a UUID".to_string());
+                }
+            }
+            FieldValidation::Length { min, max } => {
+                let len = value.as_str().map(|v| v.chars().count()).unwrap_or(0);
+
+                if len < *min || len > *max {
+                    return Err(format!(
+                        "must be between {} and {} characters long",
+                        min, max
+                    ));
+                }
+            }
+            FieldValidation::ByteLength { min, max } => {
+                let len = value.as_str().map(|v| v.len()).unwrap_or(0);
+
+                if len < *min || len > *max {
+                    return Err(format!("must be between {} and {} bytes long", min, max));
+                }
+            }
+            FieldValidation::Range { min, max } => match value.as_i64() {
+                Some(v) if v >= *min && v <= *max => {}
+                _ => return Err(format!("must be between {} and {}", min, max)),
+            },
+            // Checked against the list as a whole
+            FieldValidation::Items { .. } => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, TS, JsonSchema, Clone, Copy, EnumIter)]
+#[ts(export, export_to = ".generated/RPCFieldType.ts")]
+#[schemars(rename = "RPCFieldType")]
+pub enum FieldType {
+    Text,
+    Textarea,
+    Number,
+    Hour, // Time expressed as a number of hours
+    Boolean,
+    /// A list of strings, entered one per line (or separated by commas or spaces)
+    TextList,
+    /// Strings keyed by an ID, entered as one ``<ID>: <value>`` per line
+    TextMap,
+}
+
+impl FieldType {
+    /// Parses user input (from a modal etc.) into the JSON value ``RPCMethod`` expects for the field
+    pub fn parse(&self, input: &str) -> Result<Value, Error> {
+        match self {
+            FieldType::Text | FieldType::Textarea => Ok(Value::String(input.to_string())),
+            FieldType::Number => Ok(input.trim().parse::<i32>()?.into()),
+            FieldType::Hour => Ok(parse_hrs(input.trim())?.into()),
+            FieldType::Boolean => Ok(parse_bool(input.trim())?.into()),
+            FieldType::TextList => Ok(input
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .map(|v| Value::String(v.to_string()))
+                .collect::<Vec<_>>()
+                .into()),
+            FieldType::TextMap => parse_map(input),
+        }
+    }
+}
+
+fn parse_map(v: &str) -> Result<Value, Error> {
+    let mut map = Map::new();
+
+    for line in v.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or("Every line must be in the format <ID>: <value>")?;
+
+        map.insert(
+            key.trim().to_string(),
+            Value::String(value.trim().to_string()),
+        );
+    }
+
+    if map.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    Ok(Value::Object(map))
+}
+
+fn parse_bool(v: &str) -> Result<bool, Error> {
+    match v.to_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" => Ok(true),
+        "false" | "f" | "no" | "n" => Ok(false),
+        _ => Err("Invalid boolean".into()),
+    }
+}
+
+fn parse_hrs(v: &str) -> Result<i32, Error> {
+    // Split v into time and unit
+    let data = v.split(' ').collect::<Vec<&str>>();
+
+    if data.len() != 2 {
+        return Err(
+            "Invalid time format. Format must be WITH A SPACE BETWEEN THE NUMBER AND THE UNIT"
+                .into(),
+        );
+    }
+
+    let (time, unit) = (data[0], data[1]);
+
+    let time = time.parse::<i32>()?;
+
+    match unit {
+        "years" | "year" | "y" => Ok(time * 365 * 24),
+        "months" | "month" | "mo" | "m" => Ok(time * 30 * 24),
+        "weeks" | "week" | "w" => Ok(time * 7 * 24),
+        "days" | "day" | "d" => Ok(time * 24),
+        "hours" | "hour" | "hrs" | "hr" | "h" => Ok(time),
+        _ => Err("Invalid time format. Unit must be years, months, weeks, days or hours".into()),
+    }
+}
+
+impl RPCMethod {
+    /// The registry of all methods
+    pub fn spec(&self) -> MethodSpec {
+        match self {
+            Self::BotClaim { .. } => MethodSpec::new(
+                "Claim Bot",
+                "Claim a bot. Be sure to claim bots that you are going to review!",
+                RPCPerms::Staff,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::new(
+                "force",
+                "Force claim bot?",
+                FieldType::Boolean,
+                "fa-solid:sign-out-alt",
+                "Yes/No",
+            )),
+            Self::BotUnclaim { .. } => MethodSpec::new(
+                "Unclaim Bot",
+                "Unclaim a bot. Be sure to use this if you can't review the bot!",
+                RPCPerms::Staff,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotApprove { .. } => MethodSpec::new(
+                "Approve Bot",
+                "Approve a bot. Needs to be claimed first.",
+                RPCPerms::Staff,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotDeny { .. } => MethodSpec::new(
+                "Deny Bot",
+                "Deny a bot. Needs to be claimed first.",
+                RPCPerms::Staff,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotBulkDeny { .. } => MethodSpec::new(
+                "Bulk Deny Bots",
+                "Denies many pending bots at once without claiming them. Only for clearing out spam, review bots normally otherwise!",
+                RPCPerms::Head,
+            )
+            .since(6)
+            .field(WebField::bot_ids())
+            .field(WebField::reason().with_placeholder(BULK_REASON_PLACEHOLDER))
+            .field(WebField::bulk_reasons()),
+            Self::BotBulkUnclaim { .. } => MethodSpec::new(
+                "Bulk Unclaim Bots",
+                "Unclaims many pending bots at once",
+                RPCPerms::Head,
+            )
+            .since(6)
+            .field(WebField::bot_ids())
+            .field(WebField::reason().with_placeholder(BULK_REASON_PLACEHOLDER))
+            .field(WebField::bulk_reasons()),
+            Self::BotRequeue { .. } => MethodSpec::new(
+                "Requeue Bot",
+                "Moves a denied bot back into the queue, keeping a link to its previous denial",
+                RPCPerms::Staff,
+            )
+            .since(6)
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotVoteReset { .. } => MethodSpec::new(
+                "Reset Bot Votes",
+                "Reset the votes of a bot",
+                RPCPerms::Owner,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotVoteResetAll { .. } => MethodSpec::new(
+                "Reset All Bot Votes",
+                "Reset the votes of all bots",
+                RPCPerms::Owner,
+            )
+            .field(WebField::reason()),
+            Self::BotUnverify { .. } => MethodSpec::new(
+                "Unverify Bot",
+                "Unverifies a bot on the list",
+                RPCPerms::Staff,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason().with_placeholder("You must give proof")),
+            Self::BotPremiumAdd { .. } => MethodSpec::new(
+                "Add Premium [Bot]",
+                "Adds premium to a bot for a given time period",
+                RPCPerms::Head,
+            )
+            .field(WebField::bot_id())
+            .field(
+                WebField::new(
+                    "time_period_hours",
+                    "Time [X unit(s)]",
+                    FieldType::Hour,
+                    "material-symbols:timer",
+                    "Time period. Format: X years/days/hours",
+                )
+                .check(FieldValidation::Range {
+                    min: 1,
+                    max: 5 * 365 * 24,
+                }),
+            )
+            .field(WebField::reason().with_placeholder("You must give proof")),
+            Self::BotPremiumRemove { .. } => MethodSpec::new(
+                "Remove Premium [Bot]",
+                "Removes premium from a bot",
+                RPCPerms::Head,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason().with_placeholder("You must give proof")),
+            Self::BotVoteBanAdd { .. } => MethodSpec::new(
+                "Vote Ban Bot",
+                "Vote-bans the bot in question",
+                RPCPerms::Head,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotVoteBanRemove { .. } => MethodSpec::new(
+                "Unvote Ban Bot",
+                "Removes the vote-ban from the bot in question",
+                RPCPerms::Head,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotForceRemove { .. } => MethodSpec::new(
+                "Force Remove Bot",
+                "Forcefully removes a bot from the list",
+                RPCPerms::Admin,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::new(
+                "kick",
+                "Kick the bot from the server",
+                FieldType::Boolean,
+                "fa-solid:sign-out-alt",
+                "Yes/No",
+            ))
+            .field(WebField::reason()),
+            Self::BotCertifyAdd { .. } => MethodSpec::new(
+                "Certify Bot",
+                "Certifies a bot. Recommended to use apps instead however",
+                RPCPerms::Owner,
+            )
+            .field(WebField::bot_id())
+            .field(WebField::reason()),
+            Self::BotCertifyRemove { .. } => {
+                MethodSpec::new("Uncertify Bot", "Uncertifies a bot", RPCPerms::Owner)
+                    .field(WebField::bot_id())
+                    .field(WebField::reason())
+            }
+            Self::BotVoteCountSet { .. } => MethodSpec::new(
+                "Set Bot Vote Count",
+                "Sets the vote count of a bot",
+                RPCPerms::Owner,
+            )
+            .field(WebField::bot_id())
+            .field(
+                WebField::new(
+                    "count",
+                    "Vote count",
+                    FieldType::Number,
+                    "material-symbols:timer",
+                    "Vote count",
+                )
+                .check(FieldValidation::Range {
+                    min: 0,
+                    max: i32::MAX as i64,
+                }),
+            )
+            .field(WebField::reason()),
+            Self::BotTransferOwnershipUser { .. } => MethodSpec::new(
+                "Set Bot Owner [User]",
+                "Transfers the ownership of a bot to a new user",
+                RPCPerms::Admin,
+            )
+            .field(WebField::bot_id())
+            .field(
+                WebField::new(
+                    "new_owner",
+                    "User ID",
+                    FieldType::Text,
+                    "material-symbols:timer",
+                    "New Owner",
+                )
+                .check(FieldValidation::Snowflake),
+            )
+            .field(WebField::reason()),
+            Self::BotTransferOwnershipTeam { .. } => MethodSpec::new(
+                "Set Bot Owner [Team]",
+                "Transfers the ownership of a bot to a new team",
+                RPCPerms::Head,
+            )
+            .field(WebField::bot_id())
+            .field(
+                WebField::new(
+                    "new_team",
+                    "Team ID",
+                    FieldType::Text,
+                    "material-symbols:timer",
+                    "New Team",
+                )
+                .check(FieldValidation::Uuid),
+            )
+            .field(WebField::reason()),
+            Self::TeamNameEdit { .. } => {
+                MethodSpec::new("Edit Team Name", "Edits the name of a team", RPCPerms::Head)
+                    .field(
+                        WebField::new(
+                            "team_id",
+                            "Team ID",
+                            FieldType::Text,
+                            "material-symbols:timer",
+                            "Team ID",
+                        )
+                        .check(FieldValidation::Uuid),
+                    )
+                    .field(
+                        WebField::new(
+                            "new_name",
+                            "New team name",
+                            FieldType::Text,
+                            "material-symbols:timer",
+                            "Team name",
+                        )
+                        .check(FieldValidation::ByteLength { min: 3, max: 32 }),
+                    )
+                    .field(WebField::reason())
+            }
+            // The permissions of the method being reverted are also checked
+            Self::Revert { .. } => MethodSpec::new(
+                "Revert Action",
+                "Reverts an earlier RPC action using its log ID",
+                RPCPerms::Staff,
+            )
+            .field(
+                WebField::new(
+                    "log_id",
+                    "Log ID",
+                    FieldType::Text,
+                    "material-symbols:history",
+                    "ID of the RPC log entry to revert",
+                )
+                .check(FieldValidation::Uuid),
+            )
+            .field(WebField::reason()),
+        }
+    }
+
+    /// Checks every argument of the method against the validation spec of its field
+    ///
+    /// This runs before any SQL so bad input is rejected with an error naming the field
+    pub fn validate(&self) -> Result<(), Error> {
+        let args = match serde_json::to_value(self)? {
+            Value::Object(mut obj) => obj.remove(&self.to_string()),
+            _ => None,
+        }
+        .unwrap_or_default();
+
+        for field in self.spec().fields {
+            let value = args.get(&field.id).unwrap_or(&Value::Null);
+
+            if let Err(e) = field.validate(value) {
+                return Err(RPCFailure::invalid_field(
+                    &field.id,
+                    format!("`{}` {}", field.label, e),
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a method of the same variant from user input, one input per field of its spec
+    pub fn from_inputs(&self, inputs: &[String]) -> Result<RPCMethod, Error> {
+        let spec = self.spec();
+
+        if inputs.len() != spec.fields.len() {
+            return Err(format!(
+                "Expected {} inputs for `{}`, got {}",
+                spec.fields.len(),
+                self,
+                inputs.len()
+            )
+            .into());
+        }
+
+        let mut args = Map::new();
+
+        for (field, input) in spec.fields.iter().zip(inputs) {
+            let value = field
+                .field_type
+                .parse(input)
+                .map_err(|e| format!("Error parsing `{}`: {}", field.id, e))?;
+
+            args.insert(field.id.clone(), value);
+        }
+
+        let mut method = Map::new();
+        method.insert(self.to_string(), Value::Object(args));
+
+        Ok(serde_json::from_value(Value::Object(method))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_snowflake() {
+        let check = |v: Value| FieldValidation::Snowflake.check_value(&v);
+
+        assert!(check(json!("563808552288780322")).is_ok());
+        assert!(check(json!("1234")).is_err());
+        assert!(check(json!("56380855228878032a")).is_err());
+        assert!(check(json!("000000000000000000")).is_err());
+        assert!(check(json!(563808552288780322u64)).is_err());
+    }
+
+    #[test]
+    fn check_uuid() {
+        let check = |v: Value| FieldValidation::Uuid.check_value(&v);
+
+        assert!(check(json!("67e55044-10b1-426f-9247-bb680e5fe0c8")).is_ok());
+        assert!(check(json!("not-a-uuid")).is_err());
+        assert!(check(Value::Null).is_err());
+    }
+
+    #[test]
+    fn check_length_counts_characters() {
+        let check = |v: Value| FieldValidation::Length { min: 0, max: 3 }.check_value(&v);
+
+        assert!(check(json!("")).is_ok());
+        assert!(check(json!("äöü")).is_ok());
+        assert!(check(json!("abcd")).is_err());
+    }
+
+    #[test]
+    fn check_byte_length_counts_bytes() {
+        let check = |v: Value| FieldValidation::ByteLength { min: 3, max: 4 }.check_value(&v);
+
+        assert!(check(json!("abcd")).is_ok());
+        assert!(check(json!("äö")).is_ok());
+        assert!(check(json!("äöü")).is_err());
+        assert!(check(json!("ab")).is_err());
+    }
+
+    #[test]
+    fn check_range() {
+        let check = |v: Value| FieldValidation::Range { min: 1, max: 10 }.check_value(&v);
+
+        assert!(check(json!(1)).is_ok());
+        assert!(check(json!(10)).is_ok());
+        assert!(check(json!(0)).is_err());
+        assert!(check(json!(11)).is_err());
+        assert!(check(json!("5")).is_err());
+    }
+}
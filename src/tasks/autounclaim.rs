@@ -1,7 +1,7 @@
-use poise::serenity_prelude::{ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, UserId};
+use poise::serenity_prelude::{CreateEmbed, CreateEmbedFooter, CreateMessage, UserId};
 use std::num::NonZeroU64;
 
-use crate::config;
+use crate::{config, impls::notifications::Notification};
 
 pub async fn auto_unclaim(
     pool: &sqlx::PgPool,
@@ -79,10 +79,15 @@ pub async fn auto_unclaim(
                         .color(0xFF0000)
                 );
 
-                ChannelId(config::CONFIG.channels.testing_lounge)
-                    .send_message(&cache_http, msg)
+                Notification::new(config::CONFIG.channels.testing_lounge, msg)
+                    .dedup(format!(
+                        "autounclaim:{}:{}",
+                        bot.bot_id,
+                        last_claimed.timestamp()
+                    ))
+                    .enqueue(pool)
                     .await
-                    .map_err(|e| format!("Error while sending message in #lounge: {}", e))?;
+                    .map_err(|e| format!("Error while queueing message in #lounge: {}", e))?;
 
                 let bot_owner = crate::impls::utils::resolve_ping_user(&bot.bot_id, pool).await?;
 
@@ -1,8 +1,10 @@
 pub mod autounclaim;
 pub mod bans;
+pub mod notifications;
 pub mod premium;
 pub mod specrolesync;
 pub mod staffresync;
 pub mod taskcat;
 pub mod teamcleaner;
 pub mod uptime;
+pub mod webhooks;
@@ -0,0 +1,103 @@
+use std::num::NonZeroU64;
+
+use poise::serenity_prelude::ChannelId;
+
+use crate::impls::notifications::{
+    backoff_secs, CLAIM_SECS, KEEP_DEAD_DAYS, KEEP_SENT_DAYS, MAX_ATTEMPTS,
+};
+
+pub async fn deliver_notifications(
+    pool: &sqlx::PgPool,
+    cache_http: &crate::impls::cache::CacheHttpImpl,
+) -> Result<(), crate::Error> {
+    sqlx::query!(
+        "DELETE FROM notifications WHERE (state = 'sent' AND sent_at < NOW() - make_interval(days => $1)) OR (state = 'dead' AND created_at < NOW() - make_interval(days => $2))",
+        KEEP_SENT_DAYS,
+        KEEP_DEAD_DAYS
+    )
+    .execute(pool)
+    .await?;
+
+    // Claim the rows first by pushing their next attempt back, so other instances skip them while they are sent
+    let pending = sqlx::query!(
+        "UPDATE notifications SET next_attempt_at = NOW() + make_interval(secs => $1) WHERE id IN (SELECT id FROM notifications WHERE state = 'pending' AND next_attempt_at <= NOW() ORDER BY created_at LIMIT 50 FOR UPDATE SKIP LOCKED) RETURNING id, channel_id, payload, attempts",
+        CLAIM_SECS
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Error while fetching pending notifications: {}", e))?;
+
+    for row in pending {
+        let channel_id = match row.channel_id.parse::<NonZeroU64>() {
+            Ok(id) => id,
+            Err(e) => {
+                log::warn!("Invalid channel id for notification {}: {}", row.id, e);
+
+                sqlx::query!(
+                    "UPDATE notifications SET state = 'dead', last_error = $1 WHERE id = $2",
+                    format!("Invalid channel id: {}", e),
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+
+                continue;
+            }
+        };
+
+        let attempts = row.attempts + 1;
+
+        match cache_http
+            .http
+            .send_message(ChannelId(channel_id), Vec::new(), &row.payload)
+            .await
+        {
+            Ok(_) => {
+                sqlx::query!(
+                    "UPDATE notifications SET state = 'sent', attempts = $1, last_error = NULL, sent_at = NOW() WHERE id = $2",
+                    attempts,
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) if attempts >= MAX_ATTEMPTS => {
+                log::error!(
+                    "Giving up on notification {} after {} attempts: {}",
+                    row.id,
+                    attempts,
+                    e
+                );
+
+                sqlx::query!(
+                    "UPDATE notifications SET state = 'dead', attempts = $1, last_error = $2 WHERE id = $3",
+                    attempts,
+                    e.to_string(),
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to deliver notification {} (attempt {}): {}",
+                    row.id,
+                    attempts,
+                    e
+                );
+
+                sqlx::query!(
+                    "UPDATE notifications SET attempts = $1, last_error = $2, next_attempt_at = NOW() + make_interval(secs => $3) WHERE id = $4",
+                    attempts,
+                    e.to_string(),
+                    backoff_secs(attempts),
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
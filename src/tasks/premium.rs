@@ -1,4 +1,7 @@
-use poise::serenity_prelude::{ChannelId, CreateMessage};
+use poise::serenity_prelude::CreateMessage;
+
+use crate::impls::{notifications::Notification, webhooks};
+use crate::rpc::events::RPCEvent;
 
 pub async fn premium_remove(
     pool: &sqlx::PgPool,
@@ -23,19 +26,6 @@ pub async fn premium_remove(
     for row in res {
         log::info!("Removing premium from bot {}", row.bot_id);
 
-        sqlx::query!(
-            "UPDATE bots SET premium = false WHERE bot_id = $1",
-            row.bot_id
-        )
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            format!(
-                "Error while removing premium from bot {}: {}",
-                row.bot_id, e
-            )
-        })?;
-
         let bot_id = row
             .bot_id
             .parse()
@@ -80,9 +70,40 @@ pub async fn premium_remove(
             }
         };
 
-        ChannelId(crate::config::CONFIG.channels.mod_logs)
-            .send_message(&cache_http, CreateMessage::default().content(msg))
-            .await?;
+        // Remove premium and queue the mod log message together so neither can happen without the other
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE bots SET premium = false WHERE bot_id = $1",
+            row.bot_id
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| {
+            format!(
+                "Error while removing premium from bot {}: {}",
+                row.bot_id, e
+            )
+        })?;
+
+        Notification::new(
+            crate::config::CONFIG.channels.mod_logs,
+            CreateMessage::default().content(msg),
+        )
+        .enqueue(&mut tx)
+        .await?;
+
+        let event = RPCEvent::PremiumChanged {
+            bot_id: row.bot_id.clone(),
+            by: None,
+            premium: false,
+        };
+
+        webhooks::enqueue(&mut tx, &row.bot_id, &event).await?;
+
+        tx.commit().await?;
+
+        event.publish();
     }
 
     Ok(())
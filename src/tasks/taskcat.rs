@@ -14,6 +14,10 @@ pub enum Task {
     SpecRoleSync,
     Uptime,
     TeamCleaner,
+    Notifications,
+    RpcScheduled,
+    Webhooks,
+    RpcNonces,
 }
 
 pub async fn start_all_tasks(
@@ -57,6 +61,10 @@ async fn taskcat(
         Task::SpecRoleSync => Duration::from_secs(50),
         Task::Uptime => Duration::from_secs(90),
         Task::TeamCleaner => Duration::from_secs(600),
+        Task::Notifications => Duration::from_secs(15),
+        Task::RpcScheduled => Duration::from_secs(30),
+        Task::Webhooks => Duration::from_secs(20),
+        Task::RpcNonces => Duration::from_secs(120),
     };
 
     let task_desc = match task {
@@ -67,6 +75,10 @@ async fn taskcat(
         Task::SpecRoleSync => "Syncing special roles",
         Task::Uptime => "Uptime Checking",
         Task::TeamCleaner => "Cleaning up empty teams",
+        Task::Notifications => "Delivering queued notifications",
+        Task::RpcScheduled => "Running scheduled RPC actions",
+        Task::Webhooks => "Delivering bot owner webhooks",
+        Task::RpcNonces => "Pruning expired RPC request nonces",
     };
 
     let mut interval = tokio::time::interval(duration);
@@ -91,6 +103,12 @@ async fn taskcat(
             }
             Task::Uptime => crate::tasks::uptime::uptime_checker(&pool, &cache_http).await,
             Task::TeamCleaner => crate::tasks::teamcleaner::team_cleaner(&pool).await,
+            Task::Notifications => {
+                crate::tasks::notifications::deliver_notifications(&pool, &cache_http).await
+            }
+            Task::RpcScheduled => crate::rpc::scheduled::run_due(&pool, &cache_http).await,
+            Task::Webhooks => crate::tasks::webhooks::deliver_webhooks(&pool).await,
+            Task::RpcNonces => crate::rpc::signing::prune_nonces(&pool).await,
         } {
             log::error!("TASK {} ERROR'd: {:?}", task.to_string(), e);
         }
@@ -1,7 +1,9 @@
 use std::num::NonZeroU64;
 
 use log::info;
-use poise::serenity_prelude::{ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId};
+use poise::serenity_prelude::{CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId};
+
+use crate::impls::notifications::Notification;
 
 pub async fn uptime_checker(
     pool: &sqlx::PgPool,
@@ -92,9 +94,15 @@ pub async fn uptime_checker(
                                 .color(0x00ff00),
                         );
 
-                        ChannelId(crate::config::CONFIG.channels.uptime)
-                            .send_message(&cache_http, msg)
+                        Notification::new(crate::config::CONFIG.channels.uptime, msg)
+                            .dedup(format!("uptime:{}:{}", row.bot_id, row.total_uptime))
+                            .enqueue(pool)
                             .await?;
+
+                        crate::rpc::events::RPCEvent::UptimeWarning {
+                            bot_id: row.bot_id.clone(),
+                        }
+                        .publish();
                     }
 
                     sqlx::query!(
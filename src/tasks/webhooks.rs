@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::impls::notifications::{backoff_secs, CLAIM_SECS, KEEP_DEAD_DAYS, KEEP_SENT_DAYS};
+use crate::impls::webhooks::{
+    check_url, resolve_public, sign, DELIVERY_HEADER, EVENT_HEADER, MAX_ATTEMPTS, SIGNATURE_HEADER,
+    TIMESTAMP_HEADER,
+};
+
+/// Resolves webhook hosts like the system resolver, but never to an address that is not public
+///
+/// Checking the URL before sending is not enough on its own, the host could resolve differently by the time we connect
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent("Arcadia-Webhooks")
+        // A redirect could point anywhere, including internal addresses
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .expect("Failed to build webhook client")
+});
+
+/// Sends one webhook, returning the HTTP status (if the owner's server responded) and the error (if any)
+async fn send(
+    id: &str,
+    event: &str,
+    url: &str,
+    secret: &str,
+    body: String,
+) -> (Option<i32>, Option<String>) {
+    // The URL may have been set before these checks existed, or be an IP address which is never resolved
+    if let Err(e) = check_url(url).await {
+        return (None, Some(e.to_string()));
+    }
+
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign(secret, timestamp, &body);
+
+    let res = CLIENT
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_HEADER, event)
+        .header(DELIVERY_HEADER, id)
+        .body(body)
+        .send()
+        .await;
+
+    match res {
+        Ok(resp) => {
+            let status = resp.status();
+
+            if status.is_success() {
+                (Some(status.as_u16().into()), None)
+            } else {
+                (
+                    Some(status.as_u16().into()),
+                    Some(format!("Got status {}", status)),
+                )
+            }
+        }
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+pub async fn deliver_webhooks(pool: &sqlx::PgPool) -> Result<(), crate::Error> {
+    sqlx::query!(
+        "DELETE FROM bot_webhook_deliveries WHERE (state = 'sent' AND delivered_at < NOW() - make_interval(days => $1)) OR (state = 'dead' AND created_at < NOW() - make_interval(days => $2))",
+        KEEP_SENT_DAYS,
+        KEEP_DEAD_DAYS
+    )
+    .execute(pool)
+    .await?;
+
+    // Claim the rows first by pushing their next attempt back, so other instances skip them while they are sent.
+    // The URL and secret are read at delivery time so owners can fix a broken webhook without losing events
+    let pending = sqlx::query!(
+        "UPDATE bot_webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $1) FROM bot_webhooks w WHERE w.bot_id = d.bot_id AND d.id IN (SELECT id FROM bot_webhook_deliveries WHERE state = 'pending' AND next_attempt_at <= NOW() ORDER BY created_at LIMIT 50 FOR UPDATE SKIP LOCKED) RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret",
+        CLAIM_SECS
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Error while fetching pending webhook deliveries: {}", e))?;
+
+    for row in pending {
+        let attempts = row.attempts + 1;
+
+        let (status, error) = send(
+            &row.id.to_string(),
+            &row.event,
+            &row.url,
+            &row.secret,
+            row.payload.to_string(),
+        )
+        .await;
+
+        match error {
+            None => {
+                sqlx::query!(
+                    "UPDATE bot_webhook_deliveries SET state = 'sent', attempts = $1, response_status = $2, last_error = NULL, delivered_at = NOW() WHERE id = $3",
+                    attempts,
+                    status,
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Some(e) if attempts >= MAX_ATTEMPTS => {
+                log::warn!(
+                    "Giving up on webhook delivery {} after {} attempts: {}",
+                    row.id,
+                    attempts,
+                    e
+                );
+
+                sqlx::query!(
+                    "UPDATE bot_webhook_deliveries SET state = 'dead', attempts = $1, response_status = $2, last_error = $3 WHERE id = $4",
+                    attempts,
+                    status,
+                    e,
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+            Some(e) => {
+                sqlx::query!(
+                    "UPDATE bot_webhook_deliveries SET attempts = $1, response_status = $2, last_error = $3, next_attempt_at = NOW() + make_interval(secs => $4) WHERE id = $5",
+                    attempts,
+                    status,
+                    e,
+                    backoff_secs(attempts),
+                    row.id
+                )
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::impls::resubmissions::{self, Resubmission};
 use crate::{checks, config};
 use futures_util::StreamExt;
 use log::info;
@@ -62,11 +63,49 @@ struct InternalQueueBot {
     short: String,
     owner: String,
     invite: String,
+    resubmissions: Vec<Resubmission>,
+}
+
+/// Summarises the latest resubmissions of a bot, and the denials they answer
+fn resubmission_history(resubmissions: &[Resubmission]) -> String {
+    if resubmissions.is_empty() {
+        return "*Never resubmitted*".to_string();
+    }
+
+    let mut history = resubmissions
+        .iter()
+        .take(3)
+        .map(|r| {
+            let denial = match (&r.denied_by, &r.denial_reason) {
+                (Some(denied_by), Some(reason)) => {
+                    format!("denied by <@{}>: {}", denied_by, reason)
+                }
+                _ => "previous denial unknown".to_string(),
+            };
+
+            format!(
+                "<t:{}:d> by {} <@{}> ({}): {}",
+                r.created_at.timestamp(),
+                r.source,
+                r.requested_by,
+                denial,
+                r.note
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if resubmissions.len() > 3 {
+        history.push_str(&format!("\n...and {} more", resubmissions.len() - 3));
+    }
+
+    // Embed fields can be at most 1024 characters long
+    history.chars().take(1024).collect()
 }
 
 fn _queue_bot(qb: InternalQueueBot) -> CreateReply {
     let reply = if qb.text_msg {
-        let text_msg = format!("**{name} [{c_bot}/{bot_len}]**\n**ID:** {id}\n**Claimed by:** {claimed_by}\n**Approval note:** {approve_note}\n**Short:** {short}\n**Queue name:** {name}\n**Owner:** {owner}\n**Invite:** {invite}", 
+        let text_msg = format!("**{name} [{c_bot}/{bot_len}]**\n**ID:** {id}\n**Claimed by:** {claimed_by}\n**Approval note:** {approve_note}\n**Short:** {short}\n**Queue name:** {name}\n**Owner:** {owner}\n**Invite:** {invite}\n**Resubmissions:** {resubmissions}", 
             name = qb.queue_name,
             c_bot = qb.index + 1,
             bot_len = qb.total_bots,
@@ -75,7 +114,8 @@ fn _queue_bot(qb: InternalQueueBot) -> CreateReply {
             approve_note = qb.approval_note,
             short = qb.short,
             owner = qb.owner,
-            invite = qb.invite
+            invite = qb.invite,
+            resubmissions = resubmission_history(&qb.resubmissions)
         );
 
         CreateReply::default().content(text_msg)
@@ -99,7 +139,8 @@ fn _queue_bot(qb: InternalQueueBot) -> CreateReply {
             )
             .field("Approval note", qb.approval_note, true)
             .field("Queue name", qb.queue_name, true)
-            .field("Invite", format!("[Invite Bot]({})", qb.invite), true);
+            .field("Invite", format!("[Invite Bot]({})", qb.invite), true)
+            .field("Resubmissions", resubmission_history(&qb.resubmissions), false);
 
         CreateReply::default().embed(embed)
     };
@@ -154,6 +195,7 @@ pub async fn queue(
     let bot = &bots[current_bot];
 
     let bot_owner = crate::impls::utils::resolve_ping_user(&bot.bot_id, &data.pool).await?;
+    let resubmissions = resubmissions::history(&data.pool, &bot.bot_id).await?;
 
     let mut msg = ctx
         .send(_queue_bot(InternalQueueBot {
@@ -167,6 +209,7 @@ pub async fn queue(
             short: bot.short.clone(),
             owner: bot_owner,
             invite: bot.invite.clone(),
+            resubmissions,
         }))
         .await?
         .into_message()
@@ -207,6 +250,7 @@ pub async fn queue(
         let bot = &bots[current_bot];
 
         let bot_owner = crate::impls::utils::resolve_ping_user(&bot.bot_id, &data.pool).await?;
+        let resubmissions = resubmissions::history(&data.pool, &bot.bot_id).await?;
 
         msg.edit(
             ctx,
@@ -221,6 +265,7 @@ pub async fn queue(
                 short: bot.short.clone(),
                 owner: bot_owner,
                 invite: bot.invite.clone(),
+                resubmissions,
             })
             .to_prefix_edit(),
         )
@@ -330,6 +375,8 @@ pub async fn claim(
         pool: data.pool.clone(),
         cache_http: data.cache_http.clone(),
         user_id: ctx.author().id.to_string(),
+        dry_run: false,
+        approved_by: None,
     })
     .await?;
 
@@ -366,6 +413,8 @@ pub async fn unclaim(
         pool: data.pool.clone(),
         cache_http: data.cache_http.clone(),
         user_id: ctx.author().id.to_string(),
+        dry_run: false,
+        approved_by: None,
     })
     .await?;
 
@@ -403,6 +452,8 @@ pub async fn approve(
         pool: data.pool.clone(),
         cache_http: data.cache_http.clone(),
         user_id: ctx.author().id.to_string(),
+        dry_run: false,
+        approved_by: None,
     })
     .await?;
 
@@ -442,6 +493,8 @@ pub async fn deny(
         pool: data.pool.clone(),
         cache_http: data.cache_http.clone(),
         user_id: ctx.author().id.to_string(),
+        dry_run: false,
+        approved_by: None,
     })
     .await?;
 